@@ -0,0 +1,30 @@
+#![no_main]
+
+use std::io::Cursor;
+
+use libfuzzer_sys::fuzz_target;
+use remfab::rm2::waveform::{ParseOptions, Table};
+
+fuzz_target!(|data: &[u8]| {
+    // The CRC32 is too hard for the fuzzer to get right by chance, and would
+    // keep it from reaching the rest of the parser.
+    let options = ParseOptions {
+        verify_crc32: false,
+        lazy: true,
+        ..Default::default()
+    };
+
+    let Ok(table) = Table::parse_with_options(&mut Cursor::new(data), &options) else {
+        return;
+    };
+
+    // Decode every waveform through lookups, which is where lazy decoding
+    // reports corrupted blocks.
+    let ranges: Vec<_> = table.temperature_ranges().collect();
+    for mode in table.modes() {
+        for range in &ranges {
+            let _ = table.lookup(*mode, range.min);
+            let _ = table.lookup(*mode, range.max);
+        }
+    }
+});
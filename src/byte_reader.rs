@@ -1,45 +1,268 @@
-use std::io::{Read, Result, Seek};
-
-pub fn le_u32<R: Read>(input: &mut R) -> Result<u32> {
-    let mut buf = [0; 4];
-    input.read_exact(&mut buf)?;
-    Ok(u32::from_le_bytes(buf))
-}
+use core::fmt;
 
 pub fn u24_from_le_bytes(bytes: [u8; 3]) -> u32 {
     u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0])
 }
 
-pub fn le_u24<R: Read>(input: &mut R) -> Result<u32> {
-    let mut buf = [0; 3];
-    input.read_exact(&mut buf)?;
-    Ok(u24_from_le_bytes(buf))
+/// A field of a .wbf file, as named in a [`FieldPath`].
+///
+/// Fields are an enum rather than strings so that a [`Segment`] fits in 8
+/// bytes, which keeps errors carrying a [`Location`] small.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    Header,
+    Checksum,
+    Filesize,
+    ExtraInfoAddr,
+    Checksum1,
+    Wmta,
+    Checksum2,
+    Temperatures,
+    Filename,
+    ExtraInfo,
+    Modes,
+    Temps,
+    Pointer,
+    Waveform,
 }
 
-pub fn le_u16<R: Read>(input: &mut R) -> Result<u16> {
-    let mut buf = [0; 2];
-    input.read_exact(&mut buf)?;
-    Ok(u16::from_le_bytes(buf))
+impl Field {
+    /// The name of the field, like `extra_info_addr`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Header => "header",
+            Field::Checksum => "checksum",
+            Field::Filesize => "filesize",
+            Field::ExtraInfoAddr => "extra_info_addr",
+            Field::Checksum1 => "checksum1",
+            Field::Wmta => "wmta",
+            Field::Checksum2 => "checksum2",
+            Field::Temperatures => "temperatures",
+            Field::Filename => "filename",
+            Field::ExtraInfo => "extra_info",
+            Field::Modes => "modes",
+            Field::Temps => "temps",
+            Field::Pointer => "pointer",
+            Field::Waveform => "waveform",
+        }
+    }
 }
 
-pub fn u8<R: Read>(input: &mut R) -> Result<u8> {
-    let mut buf = [0; 1];
-    input.read_exact(&mut buf)?;
-    Ok(buf[0])
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
 }
 
-pub fn take_const<const C: usize, R: Read>(input: &mut R) -> Result<[u8; C]> {
-    let mut buf = [0; C];
-    input.read_exact(&mut buf)?;
-    Ok(buf)
+/// A segment of a [`FieldPath`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment {
+    Field(Field),
+    Index(u32),
 }
 
-pub fn take<R: Read>(count: usize, input: &mut R) -> Result<Vec<u8>> {
-    let mut buf = vec![0; count];
-    input.read_exact(&mut buf)?;
-    Ok(buf)
+/// Deepest path a [`FieldPath`] keeps, segments nested deeper are dropped.
+const MAX_DEPTH: usize = 6;
+
+/// Path to the field being read, like `modes[3].temps[5].pointer`.
+///
+/// It has a fixed capacity so that it can be used without an allocator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldPath {
+    segments: [Segment; MAX_DEPTH],
+    depth: usize,
 }
 
-pub fn skip<S: Seek>(count: i64, input: &mut S) -> Result<()> {
-    input.seek_relative(count)
+impl FieldPath {
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments[..self.depth.min(MAX_DEPTH)]
+    }
+
+    fn push(&mut self, segment: Segment) {
+        if let Some(slot) = self.segments.get_mut(self.depth) {
+            *slot = segment;
+        }
+        self.depth += 1;
+    }
+
+    fn pop(&mut self) {
+        self.depth -= 1;
+    }
+}
+
+impl Default for FieldPath {
+    fn default() -> Self {
+        FieldPath {
+            segments: [Segment::Index(0); MAX_DEPTH],
+            depth: 0,
+        }
+    }
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.segments().iter().enumerate() {
+            match segment {
+                Segment::Field(field) if i == 0 => write!(f, "{field}")?,
+                Segment::Field(field) => write!(f, ".{field}")?,
+                Segment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Where in the input something happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub offset: usize,
+    pub path: FieldPath,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.segments().is_empty() {
+            write!(f, "offset 0x{:x}", self.offset)
+        } else {
+            write!(f, "{} at offset 0x{:x}", self.path, self.offset)
+        }
+    }
+}
+
+/// Returned when reading past the end of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnexpectedEof(pub Location);
+
+pub type Result<T> = core::result::Result<T, UnexpectedEof>;
+
+/// A cursor over a byte slice that keeps track of its offset and of the path
+/// of the field being read.
+#[derive(Debug, Clone)]
+pub struct Cursor<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    path: FieldPath,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Cursor {
+            bytes,
+            offset: 0,
+            path: FieldPath::default(),
+        }
+    }
+
+    /// The whole input, regardless of the current offset.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn seek(&mut self, offset: usize) {
+        self.offset = offset;
+    }
+
+    pub fn location(&self) -> Location {
+        self.location_at(self.offset)
+    }
+
+    /// The current field path with another offset, for errors about
+    /// something that was read earlier.
+    pub fn location_at(&self, offset: usize) -> Location {
+        Location {
+            offset,
+            path: self.path,
+        }
+    }
+
+    /// Run `f` with `field` appended to the field path.
+    pub fn field<T, E>(
+        &mut self,
+        field: Field,
+        f: impl FnOnce(&mut Self) -> core::result::Result<T, E>,
+    ) -> core::result::Result<T, E> {
+        self.nested(Segment::Field(field), f)
+    }
+
+    /// Run `f` with `index` appended to the field path.
+    pub fn index<T, E>(
+        &mut self,
+        index: usize,
+        f: impl FnOnce(&mut Self) -> core::result::Result<T, E>,
+    ) -> core::result::Result<T, E> {
+        // Tables of a .wbf file have at most 256 entries.
+        self.nested(Segment::Index(index as u32), f)
+    }
+
+    fn nested<T, E>(
+        &mut self,
+        segment: Segment,
+        f: impl FnOnce(&mut Self) -> core::result::Result<T, E>,
+    ) -> core::result::Result<T, E> {
+        self.path.push(segment);
+        let result = f(self);
+        self.path.pop();
+        result
+    }
+
+    pub fn take(&mut self, count: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .offset
+            .checked_add(count)
+            .and_then(|end| self.bytes.get(self.offset..end))
+            .ok_or_else(|| UnexpectedEof(self.location()))?;
+
+        self.offset += count;
+        Ok(bytes)
+    }
+
+    pub fn take_const<const C: usize>(&mut self) -> Result<[u8; C]> {
+        let mut buf = [0; C];
+        buf.copy_from_slice(self.take(C)?);
+        Ok(buf)
+    }
+
+    pub fn le_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take_const()?))
+    }
+
+    pub fn le_u24(&mut self) -> Result<u32> {
+        Ok(u24_from_le_bytes(self.take_const()?))
+    }
+
+    pub fn le_u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take_const()?))
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.take_const::<1>()?[0])
+    }
+
+    pub fn skip(&mut self, count: usize) -> Result<()> {
+        self.take(count)?;
+        Ok(())
+    }
+}
+
+#[test]
+fn cursor_test() {
+    let mut cursor = Cursor::new(&[0x01, 0x02, 0x03, 0x04, 0x05]);
+
+    assert_eq!(cursor.le_u24(), Ok(0x030201));
+
+    let error = cursor.field(Field::Modes, |cursor| {
+        cursor.index(3, |cursor| {
+            cursor.field(Field::Pointer, |cursor| cursor.le_u32())
+        })
+    });
+
+    let location = error.unwrap_err().0;
+    assert_eq!(location.offset, 3);
+    assert_eq!(location.path.to_string(), "modes[3].pointer");
+    assert_eq!(cursor.location().path.to_string(), "");
 }
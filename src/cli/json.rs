@@ -0,0 +1,55 @@
+//! Just enough JSON writing for the output of the subcommands.
+
+use std::fmt::{self, Write as _};
+
+/// A string quoted and escaped for JSON.
+pub struct Str<'a>(pub &'a str);
+
+impl fmt::Display for Str<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('"')?;
+
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => f.write_str("\\r")?,
+                '\t' => f.write_str("\\t")?,
+                c if c < ' ' => write!(f, "\\u{:04x}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+
+        f.write_char('"')
+    }
+}
+
+/// Items separated by commas.
+pub struct List<I>(pub I);
+
+impl<I> fmt::Display for List<I>
+where
+    I: Iterator + Clone,
+    I::Item: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, item) in self.0.clone().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{item}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn str_test() {
+    assert_eq!(
+        Str("a \"b\"\n\\c\u{1}").to_string(),
+        r#""a \"b\"\n\\c\u0001""#
+    );
+    assert_eq!(format!("[{}]", List([1, 2, 3].iter())), "[1, 2, 3]");
+}
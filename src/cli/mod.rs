@@ -0,0 +1,86 @@
+//! The `remfab` command-line tool.
+
+use std::{
+    ffi::OsString,
+    io::{self, Write as _},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use remfab::rm2::waveform;
+
+mod json;
+mod render;
+mod wbf;
+mod wbf_diff;
+
+const USAGE: &str = "\
+usage: remfab <command> [<args>]
+
+commands:
+    wbf <file> [--json]    show the contents of a .wbf waveform file
+    wbf-diff <old> <new> [--transitions]
+                           show what changed between two .wbf files
+    render <file> <mode> <temperature> <directory> [--pgm] [--scale <n>]
+                           draw each frame of a waveform and an overview
+                           sheet of all of them as PNG or PGM images";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{0}")]
+    Usage(String),
+
+    #[error("cannot read {}: {source}", path.display())]
+    Read { path: PathBuf, source: io::Error },
+
+    #[error("{}: {source}", path.display())]
+    Waveform {
+        path: PathBuf,
+        source: waveform::Error,
+    },
+
+    #[error("cannot compare the waveform tables: {0}")]
+    Diff(#[source] waveform::Error),
+
+    #[error("cannot write {}: {source}", path.display())]
+    Write { path: PathBuf, source: io::Error },
+
+    #[error("cannot write output: {0}")]
+    Output(#[from] io::Error),
+}
+
+pub fn run(args: Vec<OsString>) -> ExitCode {
+    match dispatch(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Error::Usage(message)) => {
+            eprintln!("remfab: {message}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+        Err(error) => {
+            eprintln!("remfab: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn dispatch(args: Vec<OsString>) -> Result<(), Error> {
+    let mut args = args.into_iter();
+
+    let Some(command) = args.next() else {
+        return Err(Error::Usage("missing command".to_string()));
+    };
+
+    match command.to_str() {
+        Some("wbf") => wbf::run(args),
+        Some("wbf-diff") => wbf_diff::run(args),
+        Some("render") => render::run(args),
+        Some("-h" | "--help" | "help") => {
+            writeln!(io::stdout(), "{USAGE}")?;
+            Ok(())
+        }
+        _ => Err(Error::Usage(format!(
+            "unknown command: {}",
+            command.to_string_lossy()
+        ))),
+    }
+}
@@ -0,0 +1,131 @@
+//! `remfab render`: draw the frames of a waveform as images.
+
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use remfab::rm2::waveform::{Image, Table};
+
+use super::Error;
+
+/// Frames per row of the overview sheet.
+const SHEET_COLUMNS: usize = 8;
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Png,
+    Pgm,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Pgm => "pgm",
+        }
+    }
+}
+
+pub fn run(mut args: impl Iterator<Item = OsString>) -> Result<(), Error> {
+    let mut positional = vec![];
+    let mut format = Format::Png;
+    let mut scale = 4;
+
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--pgm") => format = Format::Pgm,
+            Some("--scale") => {
+                scale = args
+                    .next()
+                    .and_then(|scale| scale.to_str()?.parse().ok())
+                    .filter(|scale| *scale > 0)
+                    .ok_or_else(|| Error::Usage("--scale takes a positive integer".to_string()))?;
+            }
+            Some(option) if option.starts_with('-') => {
+                return Err(Error::Usage(format!("unknown option for render: {option}")));
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let [path, mode, temperature, directory] =
+        <[OsString; 4]>::try_from(positional).map_err(|_| {
+            Error::Usage("render takes a file, a mode, a temperature and a directory".to_string())
+        })?;
+    let path = PathBuf::from(path);
+    let directory = PathBuf::from(directory);
+
+    let temperature: u8 = temperature
+        .to_str()
+        .and_then(|temperature| temperature.parse().ok())
+        .ok_or_else(|| {
+            Error::Usage(format!(
+                "invalid temperature: {}",
+                temperature.to_string_lossy()
+            ))
+        })?;
+
+    let bytes = fs::read(&path).map_err(|source| Error::Read {
+        path: path.clone(),
+        source,
+    })?;
+    let waveform_error = |source| Error::Waveform {
+        path: path.clone(),
+        source,
+    };
+    let table = Table::from_bytes(&bytes).map_err(waveform_error)?;
+
+    let mode = table
+        .modes()
+        .iter()
+        .find(|m| m.to_string().eq_ignore_ascii_case(&mode.to_string_lossy()))
+        .copied()
+        .ok_or_else(|| Error::Usage(format!("unknown mode: {}", mode.to_string_lossy())))?;
+
+    let lookup = table.lookup(mode, temperature).map_err(waveform_error)?;
+
+    fs::create_dir_all(&directory).map_err(|source| Error::Write {
+        path: directory.clone(),
+        source,
+    })?;
+
+    for (i, matrix) in lookup.waveform.iter().enumerate() {
+        let image = Image::frame(matrix, lookup.bit_depth, scale);
+        let name = format!("frame-{i:03}.{}", format.extension());
+        write_image(&image, format, &directory.join(name))?;
+    }
+
+    let sheet = Image::sheet(lookup.waveform, lookup.bit_depth, scale, SHEET_COLUMNS);
+    let name = format!("sheet.{}", format.extension());
+    write_image(&sheet, format, &directory.join(name))?;
+
+    writeln!(
+        io::stdout(),
+        "{mode} at {temperature}°C (range {}..{}°C): {} frames written to {}",
+        lookup.range.min,
+        lookup.range.max,
+        lookup.frame_count(),
+        directory.display()
+    )?;
+
+    Ok(())
+}
+
+fn write_image(image: &Image, format: Format, path: &Path) -> Result<(), Error> {
+    let write = || {
+        let mut output = BufWriter::new(File::create(path)?);
+        match format {
+            Format::Png => image.write_png(&mut output)?,
+            Format::Pgm => image.write_pgm(&mut output)?,
+        }
+        output.flush()
+    };
+
+    write().map_err(|source| Error::Write {
+        path: path.to_path_buf(),
+        source,
+    })
+}
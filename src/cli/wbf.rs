@@ -0,0 +1,340 @@
+//! `remfab wbf`: show the contents of a .wbf waveform file.
+
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use remfab::rm2::waveform::{self, ErrorKind, FplSize, Manufacturer, ParseOptions, RunType, Table};
+
+use super::{Error, json};
+
+pub fn run(args: impl Iterator<Item = OsString>) -> Result<(), Error> {
+    let mut path = None;
+    let mut json = false;
+
+    for arg in args {
+        match arg.to_str() {
+            Some("--json") => json = true,
+            Some(option) if option.starts_with('-') => {
+                return Err(Error::Usage(format!("unknown option for wbf: {option}")));
+            }
+            _ if path.is_none() => path = Some(PathBuf::from(arg)),
+            _ => return Err(Error::Usage("wbf takes a single file".to_string())),
+        }
+    }
+
+    let path = path.ok_or_else(|| Error::Usage("missing file for wbf".to_string()))?;
+
+    let bytes = fs::read(&path).map_err(|source| Error::Read {
+        path: path.clone(),
+        source,
+    })?;
+    let report = Report::new(&bytes).map_err(|source| Error::Waveform {
+        path: path.clone(),
+        source,
+    })?;
+
+    let mut output = io::stdout().lock();
+    if json {
+        report.write_json(&mut output)?;
+    } else {
+        report.write_text(&mut output)?;
+    }
+
+    Ok(())
+}
+
+/// Whether the CRC32 stored in the header matches the contents of the file.
+enum Checksum {
+    Valid(u32),
+    Invalid { expected: u32, actual: u32 },
+}
+
+enum Value {
+    Number(u64),
+    Hex(u64),
+    Text(String),
+    Null,
+}
+
+struct Report<'a> {
+    table: Table<'a>,
+    checksum: Checksum,
+
+    /// Number of frames of each mode for each temperature range.
+    frame_counts: Vec<Vec<usize>>,
+}
+
+impl<'a> Report<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self, waveform::Error> {
+        let options = ParseOptions::default();
+
+        let (table, checksum) = match Table::from_bytes_with_options(bytes, &options) {
+            Ok(table) => {
+                let checksum = Checksum::Valid(table.info().checksum);
+                (table, checksum)
+            }
+            Err(error) => match *error.kind() {
+                ErrorKind::InvalidCrc32 { expected, actual } => {
+                    let options = ParseOptions {
+                        verify_crc32: false,
+                        ..options
+                    };
+                    let table = Table::from_bytes_with_options(bytes, &options)?;
+                    (table, Checksum::Invalid { expected, actual })
+                }
+                _ => return Err(error),
+            },
+        };
+
+        let frame_counts = (0..table.modes().len())
+            .map(|mode_index| {
+                (0..table.temperature_ranges().len())
+                    .map(|range_index| Ok(table.waveform(mode_index, range_index)?.len()))
+                    .collect()
+            })
+            .collect::<Result<_, waveform::Error>>()?;
+
+        Ok(Report {
+            table,
+            checksum,
+            frame_counts,
+        })
+    }
+
+    fn header(&self) -> Vec<(&'static str, Value)> {
+        let info = self.table.info();
+        let text = |bytes: &[u8]| Value::Text(String::from_utf8_lossy(bytes).into_owned());
+
+        vec![
+            ("filename", text(&info.filename)),
+            (
+                "extra_info",
+                info.extra_info.as_deref().map_or(Value::Null, text),
+            ),
+            ("filesize", Value::Number(info.filesize.into())),
+            ("serial", Value::Hex(info.serial.into())),
+            ("run_type", Value::Text(run_type(info.run_type))),
+            ("fpl_platform", Value::Number(info.fpl_platform.into())),
+            ("fpl_lot", Value::Number(info.fpl_lot.into())),
+            ("fpl_size", Value::Text(fpl_size(info.fpl_size))),
+            ("manufacturer", Value::Text(manufacturer(info.manufacturer))),
+            ("adhesive_run", Value::Number(info.adhesive_run.into())),
+            (
+                "waveform_version",
+                Value::Number(info.waveform_version.into()),
+            ),
+            (
+                "waveform_subversion",
+                Value::Number(info.waveform_subversion.into()),
+            ),
+            ("waveform_type", Value::Hex(info.waveform_type.into())),
+            (
+                "waveform_revision",
+                Value::Number(info.waveform_revision.into()),
+            ),
+            ("frame_rate", Value::Number(self.table.frame_rate.into())),
+            ("old_frame_rate", Value::Number(info.old_frame_rate.into())),
+            ("vcom_offset", Value::Number(info.vcom_offset.into())),
+            ("fvsn", Value::Number(info.fvsn.into())),
+            ("luts", Value::Hex(info.luts.into())),
+            (
+                "advanced_wfm_flags",
+                Value::Hex(info.advanced_wfm_flags.into()),
+            ),
+            (
+                "bit_depth",
+                Value::Number(self.table.bit_depth().bits().into()),
+            ),
+            (
+                "gray_levels",
+                Value::Number(self.table.gray_levels() as u64),
+            ),
+        ]
+    }
+
+    fn write_text<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        for (name, value) in self.header() {
+            let value = match value {
+                Value::Number(n) => n.to_string(),
+                Value::Hex(n) => format!("0x{n:x}"),
+                Value::Text(text) => text,
+                Value::Null => "-".to_string(),
+            };
+
+            writeln!(output, "{name:<20} {value}")?;
+        }
+
+        match self.checksum {
+            Checksum::Valid(crc) => writeln!(output, "{:<20} 0x{crc:08x} (valid)", "crc32")?,
+            Checksum::Invalid { expected, actual } => writeln!(
+                output,
+                "{:<20} 0x{expected:08x} (invalid, computed 0x{actual:08x})",
+                "crc32"
+            )?,
+        }
+
+        let ranges = self
+            .table
+            .temperature_ranges()
+            .map(|range| format!("{}-{}", range.min, range.max))
+            .collect::<Vec<_>>();
+
+        writeln!(output)?;
+        writeln!(output, "frames per mode and temperature range (°C):")?;
+
+        let width = ranges.iter().map(String::len).max().unwrap_or(0).max(3);
+        write!(output, "{:<8}", "")?;
+        for range in &ranges {
+            write!(output, " {range:>width$}")?;
+        }
+        writeln!(output)?;
+
+        for (mode, counts) in self.table.modes().iter().zip(&self.frame_counts) {
+            write!(output, "{:<8}", mode.to_string())?;
+            for count in counts {
+                write!(output, " {count:>width$}")?;
+            }
+            writeln!(output)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_json<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        writeln!(output, "{{")?;
+
+        for (name, value) in self.header() {
+            let value = match value {
+                Value::Number(n) | Value::Hex(n) => n.to_string(),
+                Value::Text(text) => json::Str(&text).to_string(),
+                Value::Null => "null".to_string(),
+            };
+
+            writeln!(output, "  {}: {value},", json::Str(name))?;
+        }
+
+        let (expected, actual) = match self.checksum {
+            Checksum::Valid(crc) => (crc, crc),
+            Checksum::Invalid { expected, actual } => (expected, actual),
+        };
+        writeln!(
+            output,
+            "  \"crc32\": {{\"stored\": {expected}, \"computed\": {actual}, \"valid\": {}}},",
+            expected == actual
+        )?;
+
+        let ranges = self
+            .table
+            .temperature_ranges()
+            .map(|range| format!("[{}, {}]", range.min, range.max))
+            .collect::<Vec<_>>();
+        writeln!(
+            output,
+            "  \"temperature_ranges\": [{}],",
+            json::List(ranges.iter())
+        )?;
+
+        let names = self.table.modes().iter().map(|mode| mode.to_string());
+        let modes = names.clone().map(|name| json::Str(&name).to_string());
+        writeln!(output, "  \"modes\": [{}],", json::List(modes))?;
+
+        let frame_counts = names
+            .zip(&self.frame_counts)
+            .map(|(name, counts)| format!("{}: [{}]", json::Str(&name), json::List(counts.iter())));
+        writeln!(
+            output,
+            "  \"frame_counts\": {{{}}}",
+            json::List(frame_counts)
+        )?;
+
+        writeln!(output, "}}")
+    }
+}
+
+fn run_type(run_type: RunType) -> String {
+    match run_type {
+        RunType::Baseline => "baseline".to_string(),
+        RunType::Test => "test".to_string(),
+        RunType::Production => "production".to_string(),
+        RunType::Qualification => "qualification".to_string(),
+        RunType::Unknown(code) => format!("unknown (0x{code:02x})"),
+    }
+}
+
+fn fpl_size(size: FplSize) -> String {
+    match size {
+        FplSize::Inch5_800x600 => "5\" 800x600".to_string(),
+        FplSize::Inch6_800x600 => "6\" 800x600".to_string(),
+        FplSize::Inch6_1_1024x768 => "6.1\" 1024x768".to_string(),
+        FplSize::Inch6_1024x768 => "6\" 1024x768".to_string(),
+        FplSize::Inch8_1024x768 => "8\" 1024x768".to_string(),
+        FplSize::Inch9_7_1200x825 => "9.7\" 1200x825".to_string(),
+        FplSize::Inch9_7_1600x1200 => "9.7\" 1600x1200".to_string(),
+        FplSize::Unknown(code) => format!("unknown (0x{code:02x})"),
+    }
+}
+
+fn manufacturer(manufacturer: Manufacturer) -> String {
+    let name = match manufacturer {
+        Manufacturer::Pvi(_) => "PVI",
+        Manufacturer::Lgd(_) => "LGD",
+        Manufacturer::Unknown(_) => "unknown",
+    };
+
+    format!("{name} (0x{:02x})", manufacturer.code())
+}
+
+#[test]
+fn report_test() {
+    let bytes = include_bytes!("../rm2/waveform/testdata/example.wbf");
+
+    let report = Report::new(bytes).unwrap();
+
+    let mut output = vec![];
+    report.write_text(&mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "\
+filename             test.wbf
+extra_info           test.wbf
+filesize             6971
+serial               0x12345678
+run_type             production
+fpl_platform         3
+fpl_lot              4660
+fpl_size             unknown (0x32)
+manufacturer         PVI (0x33)
+adhesive_run         25
+waveform_version     1
+waveform_subversion  2
+waveform_type        0x15
+waveform_revision    4
+frame_rate           85
+old_frame_rate       85
+vcom_offset          0
+fvsn                 1
+luts                 0x4
+advanced_wfm_flags   0x3
+bit_depth            5
+gray_levels          32
+crc32                0x4eb78aae (valid)
+
+frames per mode and temperature range (°C):
+          0-20 20-50 50-50
+INIT         1     1     1
+DU           2     3     4
+GC16         3     5     7
+"
+    );
+
+    let mut output = vec![];
+    report.write_json(&mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains(
+        "  \"temperature_ranges\": [[0, 20], [20, 50], [50, 50]],\n  \"modes\": [\"INIT\", \"DU\", \"GC16\"],\n  \"frame_counts\": {\"INIT\": [1, 1, 1], \"DU\": [2, 3, 4], \"GC16\": [3, 5, 7]}\n}\n"
+    ));
+}
@@ -0,0 +1,140 @@
+//! `remfab wbf-diff`: show what changed between two .wbf waveform files.
+
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use remfab::rm2::waveform::{Diff, ParseOptions, Table, TemperatureRange};
+
+use super::Error;
+
+pub fn run(args: impl Iterator<Item = OsString>) -> Result<(), Error> {
+    let mut paths = vec![];
+    let mut transitions = false;
+
+    for arg in args {
+        match arg.to_str() {
+            Some("--transitions") => transitions = true,
+            Some(option) if option.starts_with('-') => {
+                return Err(Error::Usage(format!(
+                    "unknown option for wbf-diff: {option}"
+                )));
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    let [old_path, new_path] = <[PathBuf; 2]>::try_from(paths)
+        .map_err(|_| Error::Usage("wbf-diff takes an old and a new file".to_string()))?;
+
+    let old_bytes = read(&old_path)?;
+    let new_bytes = read(&new_path)?;
+    let old = parse(&old_path, &old_bytes)?;
+    let new = parse(&new_path, &new_bytes)?;
+
+    // The error can come from either table, so it isn't blamed on a file.
+    let diff = Diff::new(&old, &new).map_err(Error::Diff)?;
+
+    write_diff(&diff, transitions, &mut io::stdout().lock())?;
+
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, Error> {
+    fs::read(path).map_err(|source| Error::Read {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Parse and decode a whole table without checking its CRC32, which has no
+/// say in what the waveforms are.
+fn parse<'a>(path: &Path, bytes: &'a [u8]) -> Result<Table<'a>, Error> {
+    let options = ParseOptions {
+        verify_crc32: false,
+        ..Default::default()
+    };
+
+    Table::from_bytes_with_options(bytes, &options).map_err(|source| Error::Waveform {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn range(range: TemperatureRange) -> String {
+    format!("{}-{}°C", range.min, range.max)
+}
+
+fn write_diff<W: Write>(diff: &Diff, transitions: bool, output: &mut W) -> io::Result<()> {
+    if diff.is_empty() {
+        return writeln!(output, "no differences");
+    }
+
+    if !diff.header.is_empty() {
+        writeln!(output, "header:")?;
+        for change in &diff.header {
+            writeln!(
+                output,
+                "  {}: {} -> {}",
+                change.field, change.old, change.new
+            )?;
+        }
+    }
+
+    if !diff.removed_ranges.is_empty() || !diff.added_ranges.is_empty() {
+        writeln!(output, "temperature ranges:")?;
+        for removed in &diff.removed_ranges {
+            writeln!(output, "  - {}", range(*removed))?;
+        }
+        for added in &diff.added_ranges {
+            writeln!(output, "  + {}", range(*added))?;
+        }
+    }
+
+    if !diff.removed_modes.is_empty() || !diff.added_modes.is_empty() {
+        writeln!(output, "modes:")?;
+        for removed in &diff.removed_modes {
+            writeln!(output, "  - {removed}")?;
+        }
+        for added in &diff.added_modes {
+            writeln!(output, "  + {added}")?;
+        }
+    }
+
+    if !diff.waveforms.is_empty() {
+        writeln!(output, "waveforms:")?;
+    }
+
+    for change in &diff.waveforms {
+        writeln!(
+            output,
+            "  {} {}: {} -> {} frames, {} transitions changed",
+            change.mode,
+            range(change.range),
+            change.old_frames,
+            change.new_frames,
+            change.transitions.len()
+        )?;
+
+        if !transitions {
+            continue;
+        }
+
+        for transition in &change.transitions {
+            writeln!(
+                output,
+                "    {:>2} -> {:>2}: {} -> {} frames, first change at frame {}",
+                transition.transition.from,
+                transition.transition.to,
+                transition.old_length,
+                transition.new_length,
+                transition.first_difference
+            )?;
+        }
+    }
+
+    Ok(())
+}
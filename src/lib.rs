@@ -0,0 +1,8 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![warn(clippy::unwrap_used)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+mod byte_reader;
+pub mod rm2;
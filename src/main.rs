@@ -1,6 +1,9 @@
 #![warn(clippy::unwrap_used)]
 
-mod byte_reader;
-pub mod rm2;
+use std::process::ExitCode;
 
-fn main() {}
+mod cli;
+
+fn main() -> ExitCode {
+    cli::run(std::env::args_os().skip(1).collect())
+}
@@ -1,16 +1,32 @@
 use std::{
     ffi::c_ulong,
-    fs::{self, File},
+    fmt,
+    fs::{self, File, OpenOptions},
     io,
     mem::MaybeUninit,
-    num::NonZeroU16,
+    num::{NonZeroU16, NonZeroUsize},
     os::fd::AsRawFd as _,
     path::PathBuf,
+    ptr::NonNull,
+    slice,
 };
 
-use nix::errno::Errno;
+use nix::{
+    errno::Errno,
+    libc::c_void,
+    sys::mman::{self, MapFlags, ProtFlags},
+};
 
-use crate::rm2::sy7636a_temperature;
+#[cfg(feature = "unverified-lcdif")]
+use crate::rm2::{
+    lcdif,
+    update::{self, FrameGenerator},
+    waveform::{Mode, Phase},
+};
+use crate::rm2::{
+    sy7636a_temperature,
+    waveform::{self, ParseOptions, Table},
+};
 
 use super::fb_sys::*;
 
@@ -214,6 +230,83 @@ pub struct VariableScreenInfo {
     _reserved: [u32; 4],
 }
 
+impl VariableScreenInfo {
+    /// The mode of the reMarkable 2 panel, with the timings used by waved.
+    ///
+    /// The panel is driven as a 260x1408 display of 32 bits per pixel, each
+    /// pixel carrying several panel pixels, with room for two buffers.
+    pub fn remarkable2() -> Self {
+        let bitfield = |offset| Bitfield {
+            offset,
+            length: 8,
+            msb_right: 0,
+        };
+
+        VariableScreenInfo {
+            xres: 260,
+            yres: 1408,
+            xres_virtual: 260,
+            yres_virtual: 2816,
+            xoffset: 0,
+            yoffset: 0,
+            bits_per_pixel: 32,
+            grayscale: 0,
+            red: bitfield(16),
+            green: bitfield(8),
+            blue: bitfield(0),
+            transp: bitfield(24),
+            nonstd: 0,
+            activate: fb_activate::NOW,
+            // Physical size unknown.
+            height: 0xFFFFFFFF,
+            width: 0xFFFFFFFF,
+            accel_flags: 0,
+            pixclock: 34707,
+            left_margin: 1,
+            right_margin: 1,
+            upper_margin: 4,
+            lower_margin: 12,
+            hsync_len: 1,
+            vsync_len: 1,
+            sync: 0,
+            vmode: fb_vmode::NONINTERLACED,
+            rotate: 0,
+            colorspace: 0,
+            _reserved: [0; 4],
+        }
+    }
+
+    /// Check that the timing and geometry of `actual` match `self`.
+    fn verify_mode(&self, actual: &VariableScreenInfo) -> Result<(), Error> {
+        let fields = [
+            ("xres", self.xres, actual.xres),
+            ("yres", self.yres, actual.yres),
+            ("xres_virtual", self.xres_virtual, actual.xres_virtual),
+            ("yres_virtual", self.yres_virtual, actual.yres_virtual),
+            ("bits_per_pixel", self.bits_per_pixel, actual.bits_per_pixel),
+            ("pixclock", self.pixclock, actual.pixclock),
+            ("left_margin", self.left_margin, actual.left_margin),
+            ("right_margin", self.right_margin, actual.right_margin),
+            ("upper_margin", self.upper_margin, actual.upper_margin),
+            ("lower_margin", self.lower_margin, actual.lower_margin),
+            ("hsync_len", self.hsync_len, actual.hsync_len),
+            ("vsync_len", self.vsync_len, actual.vsync_len),
+        ];
+
+        match fields
+            .into_iter()
+            .find(|(_, expected, actual)| expected != actual)
+        {
+            Some((field, expected, actual)) => Err(Error::ModeMismatch {
+                field,
+                expected,
+                actual,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
 #[repr(i32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BlankMode {
@@ -244,6 +337,25 @@ mod raw_ioctl {
     nix::ioctl_write_int_bad!(fbioblank, FBIOBLANK);
 }
 
+/// A piece of the system the driver needs, which can be discovered or given
+/// in a [`DriverConfig`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Component {
+    Framebuffer,
+    TemperatureSensor,
+    Waveform,
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Component::Framebuffer => write!(f, "mxs-lcdif framebuffer"),
+            Component::TemperatureSensor => write!(f, "SY7636A temperature sensor"),
+            Component::Waveform => write!(f, "waveform file"),
+        }
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("ioctl 0x{0:x} failed: {1}")]
@@ -251,6 +363,52 @@ pub enum Error {
 
     #[error("failed to read temperature: {0}")]
     Temperature(#[from] sy7636a_temperature::Error),
+
+    #[error("no {0} found")]
+    NotFound(Component),
+
+    #[error("failed to look for the {component}: {source}")]
+    Discover {
+        component: Component,
+        source: io::Error,
+    },
+
+    #[error("failed to open the {component} at {}: {source}", path.display())]
+    Open {
+        component: Component,
+        path: PathBuf,
+        source: io::Error,
+    },
+
+    #[error("failed to load the waveform table at {}: {source}", path.display())]
+    Waveform {
+        path: PathBuf,
+        source: waveform::Error,
+    },
+
+    #[error("display mode was not applied: {field} is {actual} instead of {expected}")]
+    ModeMismatch {
+        field: &'static str,
+        expected: u32,
+        actual: u32,
+    },
+
+    #[error("failed to map the framebuffer memory: {0}")]
+    Mmap(Errno),
+
+    #[error("framebuffer memory is {len} bytes, {required} bytes are needed for its buffers")]
+    MemoryTooSmall { len: usize, required: usize },
+
+    #[error("the driver is not started")]
+    NotStarted,
+
+    #[cfg(feature = "unverified-lcdif")]
+    #[error("no waveform for the update: {0}")]
+    Lookup(#[source] waveform::Error),
+
+    #[cfg(feature = "unverified-lcdif")]
+    #[error("invalid update: {0}")]
+    Update(#[source] update::Error),
 }
 
 pub fn get_variable_screen_info(fd: &File) -> Result<VariableScreenInfo, Error> {
@@ -289,31 +447,398 @@ pub fn set_blank_mode(fd: &File, mode: BlankMode) -> Result<(), Error> {
     Ok(())
 }
 
+/// The memory of the framebuffer, mapped into the process.
+#[derive(Debug)]
+struct Mapping {
+    ptr: NonNull<c_void>,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(fd: &File, len: usize) -> Result<Self, Error> {
+        let length = NonZeroUsize::new(len).ok_or(Error::Mmap(Errno::EINVAL))?;
+
+        let ptr = unsafe {
+            mman::mmap(
+                None,
+                length,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_SHARED,
+                fd,
+                0,
+            )
+        }
+        .map_err(Error::Mmap)?;
+
+        Ok(Mapping { ptr, len })
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        // SAFETY: the mapping is `len` bytes long and stays mapped as long as
+        // `self` lives, and borrowing `self` mutably makes this the only
+        // reference to it in the process.
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr().cast(), self.len) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // Nothing useful can be done if unmapping fails.
+        let _ = unsafe { mman::munmap(self.ptr, self.len) };
+    }
+}
+
+// SAFETY: the mapping belongs to the process, not to the thread that created
+// it, and it is only accessed through `&mut self`.
+unsafe impl Send for Mapping {}
+
+/// The buffer that is not being displayed, to draw the next frame into
+/// before calling [`Driver::page_flip`].
+#[derive(Debug)]
+pub struct BackBuffer<'a> {
+    bytes: &'a mut [u8],
+    line_length: usize,
+    width: usize,
+    height: usize,
+    bytes_per_pixel: usize,
+}
+
+impl BackBuffer<'_> {
+    /// Width of the buffer in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Height of the buffer in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Length of a row in bytes, including the padding after its pixels.
+    pub fn line_length(&self) -> usize {
+        self.line_length
+    }
+
+    /// The whole buffer, row after row, padding included.
+    pub fn bytes_mut(&mut self) -> &mut [u8] {
+        self.bytes
+    }
+
+    /// The bytes of the pixels of row `y`, without padding.
+    ///
+    /// # Panics
+    ///
+    /// If `y` is out of bounds.
+    pub fn row_mut(&mut self, y: usize) -> &mut [u8] {
+        assert!(
+            y < self.height,
+            "row {y} out of bounds for {} rows",
+            self.height
+        );
+
+        let start = y * self.line_length;
+        &mut self.bytes[start..start + self.width * self.bytes_per_pixel]
+    }
+
+    /// The pixels of row `y`, for 32 bits per pixel buffers.
+    ///
+    /// # Panics
+    ///
+    /// If `y` is out of bounds or the buffer doesn't use 32 bits per pixel.
+    pub fn pixels_mut(&mut self, y: usize) -> &mut [[u8; 4]] {
+        assert_eq!(self.bytes_per_pixel, 4, "buffer is not 32 bits per pixel");
+        self.row_mut(y).as_chunks_mut().0
+    }
+
+    /// Write the scan frame applying `phases` to the panel, see
+    /// [`lcdif::encode_frame`].
+    ///
+    /// # Panics
+    ///
+    /// If `phases` doesn't cover the panel or the buffer is too small for a
+    /// scan frame, which means [`Driver::start`] did not set the mode.
+    #[cfg(feature = "unverified-lcdif")]
+    pub fn write_phases(&mut self, phases: &[Phase]) {
+        lcdif::encode_frame(phases, self.bytes, self.line_length);
+    }
+
+    /// The bytes of the pixels of each row, without padding.
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [u8]> {
+        let row_length = self.width * self.bytes_per_pixel;
+
+        self.bytes
+            .chunks_exact_mut(self.line_length.max(1))
+            .map(move |row| &mut row[..row_length])
+    }
+}
+
+/// Paths to use instead of discovering them in [`Driver::open_with`].
+#[derive(Debug, Clone, Default)]
+pub struct DriverConfig {
+    /// The framebuffer device, like `/dev/fb0`.
+    pub framebuffer: Option<PathBuf>,
+
+    /// The `temp0` file of the SY7636A hwmon device.
+    pub temperature_sensor: Option<PathBuf>,
+
+    /// The .wbf file of the panel.
+    pub waveform: Option<PathBuf>,
+}
+
+/// Use `path`, or discover it with `discover` if it is `None`.
+fn resolve_path(
+    component: Component,
+    path: Option<PathBuf>,
+    discover: impl FnOnce() -> Result<Option<PathBuf>, io::Error>,
+) -> Result<PathBuf, Error> {
+    if let Some(path) = path {
+        return Ok(path);
+    }
+
+    match discover() {
+        Ok(Some(path)) => Ok(path),
+        Ok(None) => Err(Error::NotFound(component)),
+        // The directory to look in doesn't exist on other devices.
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Err(Error::NotFound(component)),
+        Err(source) => Err(Error::Discover { component, source }),
+    }
+}
+
 #[derive(Debug)]
 pub struct Driver {
-    fd: File,
     temperature_sensor: sy7636a_temperature::Sensor,
+    table: Table<'static>,
+    framebuffer: Framebuffer,
+}
+
+/// The framebuffer device and its buffers, apart from the rest of the driver
+/// so that they can be written to while a waveform of the table is borrowed.
+#[derive(Debug)]
+struct Framebuffer {
+    fd: File,
     front_buffer_index: i32,
     back_buffer_index: i32,
     var_screen_info: VariableScreenInfo,
+
+    /// The framebuffer memory, mapped by [`Driver::start`].
+    memory: Option<Mapping>,
+    line_length: usize,
 }
 
 impl Driver {
+    /// Open the driver, discovering the framebuffer, the temperature sensor
+    /// and the waveform file.
+    pub fn open() -> Result<Self, Error> {
+        Self::open_with(DriverConfig::default())
+    }
+
+    /// Open the driver, only discovering what `config` has no path for.
+    pub fn open_with(config: DriverConfig) -> Result<Self, Error> {
+        let fb_path = resolve_path(Component::Framebuffer, config.framebuffer, discover_path)?;
+        let sensor_path = resolve_path(
+            Component::TemperatureSensor,
+            config.temperature_sensor,
+            sy7636a_temperature::discover_path,
+        )?;
+        let waveform_path = resolve_path(
+            Component::Waveform,
+            config.waveform,
+            waveform::discover_path,
+        )?;
+
+        let fd = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&fb_path)
+            .map_err(|source| Error::Open {
+                component: Component::Framebuffer,
+                path: fb_path,
+                source,
+            })?;
+
+        let temperature_sensor =
+            sy7636a_temperature::Sensor::open_path(&sensor_path).map_err(|source| Error::Open {
+                component: Component::TemperatureSensor,
+                path: sensor_path,
+                source,
+            })?;
+
+        let mut waveform_file = File::open(&waveform_path).map_err(|source| Error::Open {
+            component: Component::Waveform,
+            path: waveform_path.clone(),
+            source,
+        })?;
+        // Only the waveforms of the modes and temperatures in use get decoded.
+        let options = ParseOptions {
+            lazy: true,
+            ..Default::default()
+        };
+        let table = Table::parse_with_options(&mut waveform_file, &options).map_err(|source| {
+            Error::Waveform {
+                path: waveform_path,
+                source,
+            }
+        })?;
+
+        let var_screen_info = get_variable_screen_info(&fd)?;
+
+        Ok(Driver {
+            temperature_sensor,
+            table,
+            framebuffer: Framebuffer {
+                fd,
+                front_buffer_index: -1,
+                back_buffer_index: 0,
+                var_screen_info,
+                memory: None,
+                line_length: 0,
+            },
+        })
+    }
+
+    /// The waveform table of the panel.
+    pub fn table(&self) -> &Table<'static> {
+        &self.table
+    }
+
+    /// The temperature of the panel in Celsius, to look up waveforms with.
+    pub fn temperature(&mut self) -> Result<u8, Error> {
+        Ok(self.temperature_sensor.read_temperature()?)
+    }
+
+    /// Unblank the display, program the mode of the panel and map the
+    /// framebuffer memory.
+    ///
+    /// The mode is set even if it is already there, so that the driver works
+    /// whether or not something else configured the display before.
     pub fn start(&mut self) -> Result<(), Error> {
-        set_blank_mode(&self.fd, BlankMode::Unblank)?;
+        self.framebuffer.start()
+    }
+
+    /// Number of buffers that fit in the virtual resolution.
+    pub fn buffer_count(&self) -> usize {
+        self.framebuffer.buffer_count()
+    }
+
+    /// The buffer to draw the next frame into, or `None` before
+    /// [`Driver::start`].
+    ///
+    /// The view borrows the driver, so the buffer can't be flipped to the
+    /// display while it is being written to.
+    pub fn back_buffer(&mut self) -> Option<BackBuffer<'_>> {
+        self.framebuffer.back_buffer()
+    }
+
+    /// Update the panel to show `target`, one intensity per panel pixel line
+    /// by line, with the waveform of `mode` at the current temperature.
+    ///
+    /// `generator` holds what the panel shows, and is updated once every
+    /// frame has been displayed. `generator` and `target` must cover the
+    /// panel, and `target` must only have intensities within the bit depth
+    /// of the waveform table.
+    ///
+    /// The last frame displayed drives no pixel, so that the panel is left
+    /// alone once the update is done.
+    #[cfg(feature = "unverified-lcdif")]
+    pub fn display(
+        &mut self,
+        generator: &mut FrameGenerator,
+        mode: Mode,
+        target: &[u8],
+    ) -> Result<(), Error> {
+        let pixels = lcdif::PANEL_WIDTH * lcdif::PANEL_HEIGHT;
+        if target.len() != pixels {
+            return Err(Error::Update(update::Error::TargetSize {
+                len: target.len(),
+                pixels,
+            }));
+        }
 
         let temperature = self.temperature_sensor.read_temperature()?;
+        let lookup = self
+            .table
+            .lookup(mode, temperature)
+            .map_err(Error::Lookup)?;
+
+        let mut update = generator.update(&lookup, target).map_err(Error::Update)?;
+        while let Some(phases) = update.next_frame() {
+            self.framebuffer
+                .back_buffer()
+                .ok_or(Error::NotStarted)?
+                .write_phases(phases);
+            self.framebuffer.page_flip()?;
+        }
+
+        // The controller keeps scanning out the buffer that was flipped last,
+        // so the update ends with a frame that leaves every pixel alone.
+        self.framebuffer
+            .back_buffer()
+            .ok_or(Error::NotStarted)?
+            .write_phases(&vec![Phase::Noop; pixels]);
+        self.framebuffer.page_flip()
+    }
+
+    /// Display the back buffer, and make the buffer that was displayed the
+    /// new back buffer.
+    pub fn page_flip(&mut self) -> Result<(), Error> {
+        self.framebuffer.page_flip()
+    }
+}
+
+impl Framebuffer {
+    fn start(&mut self) -> Result<(), Error> {
+        set_blank_mode(&self.fd, BlankMode::Unblank)?;
+
+        let mode = VariableScreenInfo::remarkable2();
+        set_variable_screen_info(&self.fd, &mode)?;
 
-        let fscreeninfo = get_fixed_screen_info(&self.fd)?;
         let vscreeninfo = get_variable_screen_info(&self.fd)?;
+        mode.verify_mode(&vscreeninfo)?;
 
+        // The line length and memory size depend on the mode.
+        let fscreeninfo = get_fixed_screen_info(&self.fd)?;
+
+        let len = fscreeninfo.smem_len as usize;
+        let line_length = fscreeninfo.line_length as usize;
+        let required = line_length * vscreeninfo.yres_virtual as usize;
+        if len < required {
+            return Err(Error::MemoryTooSmall { len, required });
+        }
+
+        self.memory = Some(Mapping::new(&self.fd, len)?);
+        self.line_length = line_length;
         self.var_screen_info = vscreeninfo;
 
+        // The mode was just set, so nothing is displayed from the buffers yet.
+        self.front_buffer_index = -1;
+        self.back_buffer_index = 0;
+
         Ok(())
     }
 
-    pub fn page_flip(&mut self) -> Result<(), Error> {
-        // self.fb_var_info.yoffset = self.back_buffer_index * self.dims.height;
+    fn buffer_count(&self) -> usize {
+        let yres = self.var_screen_info.yres.max(1);
+        (self.var_screen_info.yres_virtual / yres).max(1) as usize
+    }
+
+    fn back_buffer(&mut self) -> Option<BackBuffer<'_>> {
+        let height = self.var_screen_info.yres as usize;
+        let size = height * self.line_length;
+        let start = self.back_buffer_index as usize * size;
+
+        let memory = self.memory.as_mut()?;
+
+        Some(BackBuffer {
+            bytes: &mut memory.bytes_mut()[start..start + size],
+            line_length: self.line_length,
+            width: self.var_screen_info.xres as usize,
+            height,
+            bytes_per_pixel: self.var_screen_info.bits_per_pixel.div_ceil(8) as usize,
+        })
+    }
+
+    fn page_flip(&mut self) -> Result<(), Error> {
+        self.var_screen_info.yoffset = self.back_buffer_index as u32 * self.var_screen_info.yres;
 
         if self.front_buffer_index == -1 {
             set_variable_screen_info(&self.fd, &self.var_screen_info)
@@ -322,7 +847,7 @@ impl Driver {
         }?;
 
         self.front_buffer_index = self.back_buffer_index;
-        self.back_buffer_index = (self.back_buffer_index + 1) % 2;
+        self.back_buffer_index = (self.back_buffer_index + 1) % self.buffer_count() as i32;
 
         Ok(())
     }
@@ -342,7 +867,7 @@ pub fn discover_path() -> Result<Option<PathBuf>, io::Error> {
             continue;
         }
 
-        if &fs::read(&name_path)? == DEVICE_NAME {
+        if fs::read(&name_path)? == DEVICE_NAME {
             let mut dev_path = PathBuf::new();
             dev_path.push("/dev");
             dev_path.push(entry.file_name());
@@ -353,3 +878,19 @@ pub fn discover_path() -> Result<Option<PathBuf>, io::Error> {
 
     Ok(None)
 }
+
+#[test]
+fn verify_mode_test() {
+    let mode = VariableScreenInfo::remarkable2();
+    assert!(mode.verify_mode(&mode.clone()).is_ok());
+
+    let single_buffer = VariableScreenInfo {
+        yres_virtual: 1408,
+        ..mode.clone()
+    };
+    let error = mode.verify_mode(&single_buffer).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "display mode was not applied: yres_virtual is 1408 instead of 2816"
+    );
+}
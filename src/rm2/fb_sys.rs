@@ -43,6 +43,14 @@ pub mod fb_visual {
     pub const FOURCC: u32 = 6;
 }
 
+pub mod fb_activate {
+    pub const NOW: u32 = 0;
+}
+
+pub mod fb_vmode {
+    pub const NONINTERLACED: u32 = 0;
+}
+
 pub mod vesa {
     pub const NO_BLANKING: i32 = 0;
     pub const VSYNC_SUSPEND: i32 = 1;
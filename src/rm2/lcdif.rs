@@ -0,0 +1,247 @@
+//! Encoding of phases into the raw framebuffer scanned out by the mxs-lcdif
+//! controller to drive the reMarkable 2 panel.
+//!
+//! The controller is set up as a 260x1408 display of 32 bits per pixel (see
+//! `VariableScreenInfo::remarkable2`), and each of its pixels carries the
+//! phases of 8 panel pixels along with the control signals of the panel's
+//! source and gate drivers:
+//!
+//! - byte 0 and 1: the phases of 8 consecutive panel pixels of a line, 2
+//!   bits each in the encoding of [`Phase`], the first pixel in the high
+//!   bits of byte 1;
+//! - byte 2: the control signals, see the `CONTROL_*` constants;
+//! - byte 3: unused.
+//!
+//! The panel is scanned in its native orientation, as 1404 lines of 1872
+//! pixels. A scan frame starts with [`UPPER_LINES`] lines starting the gate
+//! driver, followed by one framebuffer line per panel line and
+//! [`LOWER_LINES`] lines of blanking. Each line starts with
+//! [`LEFT_PIXELS`] pixels of horizontal sync and source driver start before
+//! the 234 pixels of data.
+//!
+//! The region sizes and control words are modeled on the sequences waved
+//! produces, they still need to be checked byte for byte against a dump of
+//! the framebuffer of a device. Until then, the module is only built with the
+//! `unverified-lcdif` feature, and nothing encoded here should be sent to a
+//! panel. `encode_frame_dump_test` does the check, it is ignored
+//! until the dump is added as `src/rm2/testdata/lcdif_black_frame.bin`: one
+//! scan frame of [`FRAME_HEIGHT`] lines of [`FRAME_WIDTH`] pixels, with no
+//! padding, driving every panel pixel to [`Phase::Black`].
+
+use crate::rm2::waveform::Phase;
+
+/// Pixels per line of the panel.
+pub const PANEL_WIDTH: usize = 1872;
+
+/// Lines of the panel.
+pub const PANEL_HEIGHT: usize = 1404;
+
+/// Width of the framebuffer in 32-bit pixels.
+pub const FRAME_WIDTH: usize = 260;
+
+/// Height of the framebuffer in lines.
+pub const FRAME_HEIGHT: usize = 1408;
+
+/// Bytes per framebuffer pixel.
+pub const BYTES_PER_PIXEL: usize = 4;
+
+/// Panel pixels carried by each framebuffer pixel.
+pub const PANEL_PIXELS_PER_PIXEL: usize = 8;
+
+/// Lines at the start of a scan frame, before the first panel line.
+pub const UPPER_LINES: usize = 3;
+
+/// Lines at the end of a scan frame, after the last panel line.
+pub const LOWER_LINES: usize = 1;
+
+/// Pixels at the start of each line, before the data of the panel line.
+pub const LEFT_PIXELS: usize = HSYNC_PIXELS + SOURCE_START_PIXELS + SETUP_PIXELS;
+
+/// Pixels of horizontal sync at the start of each line.
+const HSYNC_PIXELS: usize = 8;
+
+/// Pixels carrying the source driver start pulse, on the lines that carry
+/// data, or the gate driver start pulse, on the upper lines.
+const SOURCE_START_PIXELS: usize = 11;
+
+/// Pixels between the start pulse and the data.
+const SETUP_PIXELS: usize = 7;
+
+/// Pixels of data per line.
+const DATA_PIXELS: usize = PANEL_WIDTH / PANEL_PIXELS_PER_PIXEL;
+
+/// Control bits set in every pixel of the frame.
+pub const CONTROL_BASE: u8 = 0x41;
+
+/// Latches the data into the source driver.
+pub const CONTROL_SOURCE_LATCH: u8 = 0x02;
+
+/// Lets the source driver output the latched data to the panel.
+pub const CONTROL_SOURCE_OUTPUT: u8 = 0x10;
+
+/// Start pulse of the gate driver, or of the source driver on data lines.
+pub const CONTROL_START_PULSE: u8 = 0x20;
+
+const _: () = assert!(LEFT_PIXELS + DATA_PIXELS == FRAME_WIDTH);
+const _: () = assert!(UPPER_LINES + PANEL_HEIGHT + LOWER_LINES == FRAME_HEIGHT);
+
+/// Control byte of the pixel at column `x` of framebuffer line `y`.
+fn control(x: usize, y: usize) -> u8 {
+    let data_line = (UPPER_LINES..UPPER_LINES + PANEL_HEIGHT).contains(&y);
+    let gate_start = (1..UPPER_LINES).contains(&y);
+
+    match x {
+        x if x < HSYNC_PIXELS => CONTROL_BASE,
+        x if x < HSYNC_PIXELS + SOURCE_START_PIXELS => {
+            if data_line || gate_start {
+                CONTROL_BASE | CONTROL_START_PULSE
+            } else {
+                CONTROL_BASE
+            }
+        }
+        x if x < LEFT_PIXELS => CONTROL_BASE | (data_line as u8 * CONTROL_SOURCE_OUTPUT),
+        _ if data_line => CONTROL_BASE | CONTROL_SOURCE_OUTPUT | CONTROL_SOURCE_LATCH,
+        _ if y < UPPER_LINES => CONTROL_BASE | CONTROL_SOURCE_LATCH,
+        _ => CONTROL_BASE,
+    }
+}
+
+/// Pack the phases of 8 panel pixels, the first one in the high bits.
+fn pack(phases: &[Phase]) -> u16 {
+    phases
+        .iter()
+        .fold(0, |word, phase| (word << 2) | *phase as u16)
+}
+
+/// Write one scan frame applying `phases` to the panel into `buffer`, a
+/// framebuffer of [`FRAME_HEIGHT`] lines of `line_length` bytes.
+///
+/// `phases` holds the phase of every panel pixel, line by line.
+///
+/// # Panics
+///
+/// If `phases` doesn't hold [`PANEL_WIDTH`] times [`PANEL_HEIGHT`] phases,
+/// or `buffer` is too small.
+pub fn encode_frame(phases: &[Phase], buffer: &mut [u8], line_length: usize) {
+    assert_eq!(
+        phases.len(),
+        PANEL_WIDTH * PANEL_HEIGHT,
+        "phases don't cover the panel"
+    );
+    assert!(
+        line_length >= FRAME_WIDTH * BYTES_PER_PIXEL && buffer.len() >= line_length * FRAME_HEIGHT,
+        "buffer too small for a scan frame"
+    );
+
+    for (y, line) in buffer
+        .chunks_exact_mut(line_length)
+        .take(FRAME_HEIGHT)
+        .enumerate()
+    {
+        let panel_line = y
+            .checked_sub(UPPER_LINES)
+            .filter(|line| *line < PANEL_HEIGHT)
+            .map(|line| &phases[line * PANEL_WIDTH..(line + 1) * PANEL_WIDTH]);
+
+        let pixels = line[..FRAME_WIDTH * BYTES_PER_PIXEL]
+            .as_chunks_mut::<BYTES_PER_PIXEL>()
+            .0;
+
+        for (x, pixel) in pixels.iter_mut().enumerate() {
+            let data = match (x.checked_sub(LEFT_PIXELS), panel_line) {
+                (Some(column), Some(panel_line)) => {
+                    let start = column * PANEL_PIXELS_PER_PIXEL;
+                    pack(&panel_line[start..start + PANEL_PIXELS_PER_PIXEL])
+                }
+                _ => 0,
+            };
+
+            let [low, high] = data.to_le_bytes();
+            *pixel = [low, high, control(x, y), 0];
+        }
+    }
+}
+
+#[test]
+fn encode_frame_test() {
+    let line_length = FRAME_WIDTH * BYTES_PER_PIXEL;
+    let mut buffer = vec![0xAA; line_length * FRAME_HEIGHT];
+
+    let mut phases = vec![Phase::Noop; PANEL_WIDTH * PANEL_HEIGHT];
+    phases[0] = Phase::Black;
+    phases[PANEL_WIDTH * PANEL_HEIGHT - 1] = Phase::White;
+
+    encode_frame(&phases, &mut buffer, line_length);
+
+    let pixel = |x: usize, y: usize| {
+        let start = y * line_length + x * BYTES_PER_PIXEL;
+        <[u8; 4]>::try_from(&buffer[start..start + 4]).unwrap()
+    };
+
+    // Every pixel has the base control bits and an unused last byte.
+    for y in 0..FRAME_HEIGHT {
+        for x in 0..FRAME_WIDTH {
+            let [_, _, control, unused] = pixel(x, y);
+            assert_eq!(control & CONTROL_BASE, CONTROL_BASE, "({x}, {y})");
+            assert_eq!(unused, 0);
+        }
+    }
+
+    // Blanking and sync regions carry no data.
+    for y in 0..FRAME_HEIGHT {
+        for x in 0..LEFT_PIXELS {
+            assert_eq!(pixel(x, y)[..2], [0, 0]);
+        }
+    }
+    for y in (0..UPPER_LINES).chain(FRAME_HEIGHT - LOWER_LINES..FRAME_HEIGHT) {
+        for x in 0..FRAME_WIDTH {
+            assert_eq!(pixel(x, y)[..2], [0, 0]);
+            assert_eq!(pixel(x, y)[2] & CONTROL_SOURCE_OUTPUT, 0);
+        }
+    }
+
+    // The gate driver is started on the upper lines, not on the first one.
+    assert_eq!(pixel(HSYNC_PIXELS, 0)[2] & CONTROL_START_PULSE, 0);
+    assert_ne!(pixel(HSYNC_PIXELS, 1)[2] & CONTROL_START_PULSE, 0);
+
+    // Data lines output their data.
+    let [.., control, _] = pixel(LEFT_PIXELS, UPPER_LINES);
+    assert_eq!(
+        control,
+        CONTROL_BASE | CONTROL_SOURCE_OUTPUT | CONTROL_SOURCE_LATCH
+    );
+
+    // The first and last panel pixels end up at the first and last data
+    // pixels.
+    assert_eq!(pixel(LEFT_PIXELS, UPPER_LINES)[..2], [0x00, 0b01_000000]);
+    assert_eq!(
+        pixel(FRAME_WIDTH - 1, FRAME_HEIGHT - LOWER_LINES - 1)[..2],
+        [0b10, 0x00]
+    );
+    assert_eq!(pixel(LEFT_PIXELS + 1, UPPER_LINES)[..2], [0, 0]);
+}
+
+#[test]
+#[ignore = "needs a framebuffer dump captured from a device"]
+fn encode_frame_dump_test() {
+    let path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/rm2/testdata/lcdif_black_frame.bin"
+    );
+    let dump = std::fs::read(path).unwrap();
+
+    let line_length = FRAME_WIDTH * BYTES_PER_PIXEL;
+    let mut buffer = vec![0; line_length * FRAME_HEIGHT];
+    let phases = vec![Phase::Black; PANEL_WIDTH * PANEL_HEIGHT];
+    encode_frame(&phases, &mut buffer, line_length);
+
+    assert_eq!(dump.len(), buffer.len());
+    if let Some(i) = buffer.iter().zip(&dump).position(|(a, b)| a != b) {
+        let (y, x) = (i / line_length, i % line_length / BYTES_PER_PIXEL);
+        panic!(
+            "pixel ({x}, {y}) differs from the dump: {:02x?} instead of {:02x?}",
+            &buffer[i - i % BYTES_PER_PIXEL..][..BYTES_PER_PIXEL],
+            &dump[i - i % BYTES_PER_PIXEL..][..BYTES_PER_PIXEL],
+        );
+    }
+}
@@ -1,5 +1,12 @@
 mod checksum;
+#[cfg(feature = "std")]
 pub mod fb;
+#[cfg(feature = "std")]
 mod fb_sys;
+#[cfg(feature = "unverified-lcdif")]
+pub mod lcdif;
+#[cfg(feature = "std")]
 pub mod sy7636a_temperature;
+#[cfg(feature = "alloc")]
+pub mod update;
 pub mod waveform;
@@ -75,11 +75,16 @@ impl Sensor {
     }
 }
 
-const DEVICE_NAME: &[u8; 19] = b"sy7636a_temperature";
+const DEVICE_NAME: &[u8; 20] = b"sy7636a_temperature\n";
 const HWMON_PATH: &str = "/sys/class/hwmon";
 
 pub fn discover_path() -> Result<Option<PathBuf>, io::Error> {
-    for entry in fs::read_dir(HWMON_PATH)? {
+    discover_path_in(Path::new(HWMON_PATH))
+}
+
+/// Look for the sensor among the hwmon devices of `hwmon_path`.
+fn discover_path_in(hwmon_path: &Path) -> Result<Option<PathBuf>, io::Error> {
+    for entry in fs::read_dir(hwmon_path)? {
         let entry = entry?;
         let dir_path = entry.path();
         let name_path = dir_path.join("name");
@@ -88,7 +93,7 @@ pub fn discover_path() -> Result<Option<PathBuf>, io::Error> {
             continue;
         }
 
-        if &fs::read(name_path)? != DEVICE_NAME {
+        if fs::read(name_path)? != DEVICE_NAME {
             continue;
         }
 
@@ -101,3 +106,22 @@ pub fn discover_path() -> Result<Option<PathBuf>, io::Error> {
 
     Ok(None)
 }
+
+#[test]
+fn discover_path_test() {
+    let hwmon_path = std::env::temp_dir().join(format!("remfab-hwmon-{}", std::process::id()));
+    let other = hwmon_path.join("hwmon0");
+    let sensor = hwmon_path.join("hwmon1");
+    fs::create_dir_all(&other).unwrap();
+    fs::create_dir_all(&sensor).unwrap();
+
+    // sysfs name files end with a newline.
+    fs::write(other.join("name"), "rtc\n").unwrap();
+    fs::write(other.join("temp0"), "20\n").unwrap();
+    fs::write(sensor.join("name"), "sy7636a_temperature\n").unwrap();
+    fs::write(sensor.join("temp0"), "20\n").unwrap();
+
+    let found = discover_path_in(&hwmon_path);
+    fs::remove_dir_all(&hwmon_path).unwrap();
+    assert_eq!(found.unwrap(), Some(sensor.join("temp0")));
+}
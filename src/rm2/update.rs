@@ -0,0 +1,230 @@
+//! Generation of the phase frames that take the panel from the image it shows
+//! to a new one.
+//!
+//! The panel has no memory of what it displays, so a [`FrameGenerator`] keeps
+//! the intensity of every pixel and looks up the transition each of them
+//! goes through in the waveform of the mode of the update.
+
+use alloc::{vec, vec::Vec};
+
+use crate::rm2::waveform::{
+    INTENSITY_VALUES, Lookup, Phase, PhaseMatrix, Transition, TransitionPhases,
+};
+
+/// Why [`FrameGenerator::update`] can't start an update.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Error {
+    #[error("target has {len} intensities for {pixels} pixels")]
+    TargetSize { len: usize, pixels: usize },
+
+    #[error("intensity out of bounds for {levels} gray levels: {intensity}")]
+    IntensityOutOfRange { intensity: u8, levels: usize },
+}
+
+/// Keeps the intensities shown by the panel and generates the phase frames
+/// of the updates changing them.
+#[derive(Debug, Clone)]
+pub struct FrameGenerator {
+    intensities: Vec<u8>,
+    phases: Vec<Phase>,
+}
+
+impl FrameGenerator {
+    /// A generator for a panel currently showing `intensities`, one per
+    /// pixel.
+    ///
+    /// Nothing tells what the panel shows when it is powered on, so the first
+    /// update should use a mode like [`Mode::INIT`] which drives every pixel
+    /// to a known intensity whatever it comes from.
+    ///
+    /// [`Mode::INIT`]: crate::rm2::waveform::Mode::INIT
+    pub fn new(intensities: Vec<u8>) -> Self {
+        FrameGenerator {
+            phases: vec![Phase::Noop; intensities.len()],
+            intensities,
+        }
+    }
+
+    /// The intensities shown by the panel once the last update finished.
+    pub fn intensities(&self) -> &[u8] {
+        &self.intensities
+    }
+
+    /// Start an update taking every pixel to its intensity in `target` with
+    /// the waveform of `lookup`.
+    ///
+    /// The frames after the last one driving one of the pixels are left out,
+    /// so the update takes as many frames as [`Estimate::frames`] for the
+    /// transitions of its pixels rather than the whole waveform.
+    ///
+    /// Fails if `target` doesn't have one intensity per pixel, or if the
+    /// intensities shown or targeted are out of bounds for the bit depth of
+    /// the waveform.
+    ///
+    /// [`Estimate::frames`]: crate::rm2::waveform::Estimate::frames
+    pub fn update<'a>(
+        &'a mut self,
+        lookup: &Lookup<'a>,
+        target: &'a [u8],
+    ) -> Result<Update<'a>, Error> {
+        if target.len() != self.intensities.len() {
+            return Err(Error::TargetSize {
+                len: target.len(),
+                pixels: self.intensities.len(),
+            });
+        }
+
+        let levels = lookup.bit_depth.gray_levels();
+        if let Some(intensity) = self
+            .intensities
+            .iter()
+            .chain(target)
+            .find(|i| **i as usize >= levels)
+        {
+            return Err(Error::IntensityOutOfRange {
+                intensity: *intensity,
+                levels,
+            });
+        }
+
+        let mut used = [[false; INTENSITY_VALUES]; INTENSITY_VALUES];
+        for (from, to) in self.intensities.iter().zip(target) {
+            used[*from as usize][*to as usize] = true;
+        }
+
+        let frames = Transition::all()
+            .filter(|transition| used[transition.from as usize][transition.to as usize])
+            .map(|transition| TransitionPhases::new(lookup.waveform, transition).active_len())
+            .max()
+            .unwrap_or(0);
+
+        Ok(Update {
+            generator: self,
+            frames: &lookup.waveform[..frames],
+            target,
+            frame: 0,
+        })
+    }
+}
+
+/// The frames of an update, as returned by [`FrameGenerator::update`].
+///
+/// Once every frame has been generated, the intensities of the generator are
+/// replaced with the target. An update dropped before that leaves them
+/// untouched, and the panel in an unknown state.
+#[derive(Debug)]
+pub struct Update<'a> {
+    generator: &'a mut FrameGenerator,
+    frames: &'a [PhaseMatrix],
+    target: &'a [u8],
+    frame: usize,
+}
+
+impl Update<'_> {
+    /// Number of frames of the update, up to the last one driving one of
+    /// its pixels.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Number of frames left to generate.
+    pub fn remaining(&self) -> usize {
+        self.frames.len() - self.frame
+    }
+
+    /// The phase of every pixel in the next frame, or `None` once the update
+    /// is done.
+    pub fn next_frame(&mut self) -> Option<&[Phase]> {
+        let Some(matrix) = self.frames.get(self.frame) else {
+            self.generator.intensities.copy_from_slice(self.target);
+            return None;
+        };
+        self.frame += 1;
+
+        let pixels = self.generator.intensities.iter().zip(self.target);
+        for (phase, (from, to)) in self.generator.phases.iter_mut().zip(pixels) {
+            *phase = matrix.phase(Transition {
+                from: *from,
+                to: *to,
+            });
+        }
+
+        Some(&self.generator.phases)
+    }
+}
+
+#[test]
+fn update_test() {
+    use crate::rm2::waveform::{Mode, PhaseMatrix, Table, WaveformInfo};
+
+    let mut first = PhaseMatrix::new();
+    first.set(5, 0, Phase::White);
+    first.set(0, 5, Phase::Black);
+    let mut second = PhaseMatrix::new();
+    second.set(5, 0, Phase::White);
+
+    let table = Table::new(
+        WaveformInfo::example(),
+        vec![0, 50],
+        vec![vec![vec![first, second]]],
+    );
+    let lookup = table.lookup(Mode::INIT, 20).unwrap();
+
+    let mut generator = FrameGenerator::new(vec![0, 5, 5]);
+    let target = [5, 0, 5];
+
+    let mut update = generator.update(&lookup, &target).unwrap();
+    assert_eq!(update.frame_count(), 2);
+    assert_eq!(
+        update.next_frame(),
+        Some(&[Phase::White, Phase::Black, Phase::Noop][..])
+    );
+    assert_eq!(
+        update.next_frame(),
+        Some(&[Phase::White, Phase::Noop, Phase::Noop][..])
+    );
+    assert_eq!(update.remaining(), 0);
+    assert_eq!(update.next_frame(), None);
+    assert_eq!(generator.intensities(), [5, 0, 5]);
+
+    // The second frame doesn't drive pixels going from 5 to 0, so it is
+    // left out.
+    let target = [0, 0, 0];
+    let mut update = generator.update(&lookup, &target).unwrap();
+    assert_eq!(update.frame_count(), 1);
+    assert_eq!(
+        update.next_frame(),
+        Some(&[Phase::Black, Phase::Noop, Phase::Black][..])
+    );
+
+    // An update that isn't finished doesn't change the intensities.
+    assert_eq!(generator.intensities(), [5, 0, 5]);
+
+    let target = [5, 0, 5];
+    assert_eq!(generator.update(&lookup, &target).unwrap().frame_count(), 0);
+}
+
+#[test]
+fn update_error_test() {
+    use crate::rm2::waveform::{Mode, Table, WaveformInfo};
+
+    let table = Table::new(
+        WaveformInfo::example(),
+        vec![0, 50],
+        vec![vec![vec![PhaseMatrix::new()]]],
+    );
+    let lookup = table.lookup(Mode::INIT, 20).unwrap();
+    let mut generator = FrameGenerator::new(vec![0, 5, 5]);
+
+    assert_eq!(
+        generator.update(&lookup, &[0, 5]).unwrap_err(),
+        Error::TargetSize { len: 2, pixels: 3 }
+    );
+    assert_eq!(
+        generator.update(&lookup, &[0, 32, 5]).unwrap_err(),
+        Error::IntensityOutOfRange {
+            intensity: 32,
+            levels: 32
+        }
+    );
+}
@@ -0,0 +1,289 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use super::{
+    Error, Mode, Phase, Table, TemperatureRange, Transition, TransitionPhases, WaveformInfo,
+};
+
+/// A header field that differs between two tables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+/// A transition whose phases differ between two versions of a waveform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransitionChange {
+    pub transition: Transition,
+
+    /// Number of frames up to the last one driving the pixel, in the old
+    /// and new waveforms.
+    pub old_length: usize,
+    pub new_length: usize,
+
+    /// The first frame with a different phase.
+    pub first_difference: usize,
+}
+
+/// A waveform that differs between two tables, for a mode and temperature
+/// range they both have.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaveformChange {
+    pub mode: Mode,
+    pub range: TemperatureRange,
+    pub old_frames: usize,
+    pub new_frames: usize,
+
+    /// The transitions whose phases changed, a waveform that only got
+    /// longer or shorter with no-op frames having none.
+    pub transitions: Vec<TransitionChange>,
+}
+
+/// Differences between an old and a new waveform table, as returned by
+/// [`Diff::new`].
+///
+/// Modes are matched by name and temperature ranges by their bounds, so a
+/// range that got split or moved shows up as removed and added. Transitions
+/// are only compared over the gray levels both tables have.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Diff {
+    /// Header fields that changed, except for the ones describing the layout
+    /// of the file, which can't be compared.
+    pub header: Vec<FieldChange>,
+
+    pub removed_ranges: Vec<TemperatureRange>,
+    pub added_ranges: Vec<TemperatureRange>,
+
+    pub removed_modes: Vec<Mode>,
+    pub added_modes: Vec<Mode>,
+
+    /// The waveforms that changed, by mode then temperature range.
+    pub waveforms: Vec<WaveformChange>,
+}
+
+/// The header fields compared by [`Diff::new`].
+fn header_fields(table: &Table<'_>) -> Vec<(&'static str, String)> {
+    let info: &WaveformInfo = table.info();
+    let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+
+    vec![
+        ("serial", format!("0x{:x}", info.serial)),
+        ("run_type", format!("{:?}", info.run_type)),
+        ("fpl_platform", info.fpl_platform.to_string()),
+        ("fpl_lot", info.fpl_lot.to_string()),
+        ("adhesive_run", info.adhesive_run.to_string()),
+        ("waveform_version", info.waveform_version.to_string()),
+        ("waveform_subversion", info.waveform_subversion.to_string()),
+        ("waveform_type", format!("0x{:x}", info.waveform_type)),
+        ("fpl_size", format!("{:?}", info.fpl_size)),
+        ("manufacturer", format!("{:?}", info.manufacturer)),
+        ("waveform_revision", info.waveform_revision.to_string()),
+        ("frame_rate", table.frame_rate.to_string()),
+        ("old_frame_rate", info.old_frame_rate.to_string()),
+        ("vcom_offset", info.vcom_offset.to_string()),
+        ("fvsn", info.fvsn.to_string()),
+        ("luts", format!("0x{:x}", info.luts)),
+        (
+            "advanced_wfm_flags",
+            format!("0x{:x}", info.advanced_wfm_flags),
+        ),
+        ("eb", info.eb.to_string()),
+        ("sb", info.sb.to_string()),
+        ("bit_depth", table.bit_depth().bits().to_string()),
+        ("filename", text(&info.filename)),
+        (
+            "extra_info",
+            info.extra_info.as_deref().map_or("none".to_string(), text),
+        ),
+    ]
+}
+
+/// The phases of a transition followed by no-ops forever.
+fn padded(phases: TransitionPhases<'_>) -> impl Iterator<Item = Phase> + '_ {
+    phases.chain(core::iter::repeat(Phase::Noop))
+}
+
+/// Compare the phases of one transition, frames past the end of the shorter
+/// waveform counting as no-ops.
+fn compare(
+    transition: Transition,
+    old: TransitionPhases<'_>,
+    new: TransitionPhases<'_>,
+) -> Option<TransitionChange> {
+    let length = old.len().max(new.len());
+
+    let first_difference = padded(old.clone())
+        .zip(padded(new.clone()))
+        .take(length)
+        .position(|(old, new)| old != new)?;
+
+    Some(TransitionChange {
+        transition,
+        old_length: old.active_len(),
+        new_length: new.active_len(),
+        first_difference,
+    })
+}
+
+impl Diff {
+    /// Compare every part of two tables.
+    ///
+    /// It fails if a waveform of one of the tables can't be decoded.
+    pub fn new(old: &Table<'_>, new: &Table<'_>) -> Result<Self, Error> {
+        let mut diff = Diff::default();
+
+        for ((field, old_value), (_, new_value)) in
+            header_fields(old).into_iter().zip(header_fields(new))
+        {
+            if old_value != new_value {
+                diff.header.push(FieldChange {
+                    field,
+                    old: old_value,
+                    new: new_value,
+                });
+            }
+        }
+
+        let old_ranges: Vec<_> = old.temperature_ranges().collect();
+        let new_ranges: Vec<_> = new.temperature_ranges().collect();
+        diff.removed_ranges = missing(&old_ranges, &new_ranges);
+        diff.added_ranges = missing(&new_ranges, &old_ranges);
+
+        diff.removed_modes = missing(old.modes(), new.modes());
+        diff.added_modes = missing(new.modes(), old.modes());
+
+        let levels = old.gray_levels().min(new.gray_levels()) as u8;
+
+        for (old_mode_index, mode) in old.modes().iter().enumerate() {
+            let Some(new_mode_index) = new.modes().iter().position(|m| m == mode) else {
+                continue;
+            };
+
+            for (old_range_index, range) in old_ranges.iter().enumerate() {
+                let Some(new_range_index) = new_ranges.iter().position(|r| r == range) else {
+                    continue;
+                };
+
+                let old_waveform = old.waveform(old_mode_index, old_range_index)?;
+                let new_waveform = new.waveform(new_mode_index, new_range_index)?;
+
+                if old_waveform == new_waveform {
+                    continue;
+                }
+
+                let transitions = (0..levels)
+                    .flat_map(|from| (0..levels).map(move |to| Transition { from, to }))
+                    .filter_map(|transition| {
+                        compare(
+                            transition,
+                            TransitionPhases::new(old_waveform, transition),
+                            TransitionPhases::new(new_waveform, transition),
+                        )
+                    })
+                    .collect();
+
+                diff.waveforms.push(WaveformChange {
+                    mode: *mode,
+                    range: *range,
+                    old_frames: old_waveform.len(),
+                    new_frames: new_waveform.len(),
+                    transitions,
+                });
+            }
+        }
+
+        Ok(diff)
+    }
+
+    /// Whether the two tables hold the same waveforms with the same header.
+    pub fn is_empty(&self) -> bool {
+        *self == Diff::default()
+    }
+}
+
+/// The items of `items` that are not in `others`.
+fn missing<T: PartialEq + Copy>(items: &[T], others: &[T]) -> Vec<T> {
+    items
+        .iter()
+        .filter(|item| !others.contains(item))
+        .copied()
+        .collect()
+}
+
+#[test]
+fn diff_test() {
+    use super::PhaseMatrix;
+
+    let mut matrix = PhaseMatrix::new();
+    matrix.set(3, 7, Phase::Black);
+    matrix.set(7, 3, Phase::White);
+
+    let mut changed = matrix.clone();
+    changed.set(3, 7, Phase::White);
+
+    let waveform = vec![matrix.clone(), PhaseMatrix::new()];
+    let old = Table::new(
+        WaveformInfo::example(),
+        vec![0, 20, 50],
+        vec![vec![waveform.clone(); 2]; 3],
+    );
+
+    let mut new_waveforms = vec![vec![waveform.clone(); 2]; 4];
+    new_waveforms[1][0] = vec![PhaseMatrix::new(), changed];
+    new_waveforms[2][1] = vec![matrix];
+    let new = Table::new(
+        WaveformInfo {
+            serial: 1,
+            ..WaveformInfo::example()
+        },
+        vec![0, 20, 40],
+        new_waveforms,
+    );
+
+    let diff = Diff::new(&old, &new).unwrap();
+
+    assert_eq!(
+        diff.header,
+        [FieldChange {
+            field: "serial",
+            old: "0x12345678".to_string(),
+            new: "0x1".to_string(),
+        }]
+    );
+    assert_eq!(diff.removed_ranges, [TemperatureRange { min: 20, max: 50 }]);
+    assert_eq!(diff.added_ranges, [TemperatureRange { min: 20, max: 40 }]);
+    assert_eq!(diff.removed_modes, []);
+    assert_eq!(diff.added_modes, [Mode::GL16]);
+
+    assert_eq!(diff.waveforms.len(), 1);
+    let change = &diff.waveforms[0];
+    assert_eq!(
+        (change.mode, change.range),
+        (Mode::DU, TemperatureRange { min: 0, max: 20 })
+    );
+    assert_eq!(
+        change.transitions,
+        [
+            TransitionChange {
+                transition: Transition { from: 3, to: 7 },
+                old_length: 1,
+                new_length: 2,
+                first_difference: 0,
+            },
+            TransitionChange {
+                transition: Transition { from: 7, to: 3 },
+                old_length: 1,
+                new_length: 2,
+                first_difference: 0,
+            },
+        ]
+    );
+
+    assert!(Diff::new(&old, &old).unwrap().is_empty());
+}
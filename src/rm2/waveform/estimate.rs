@@ -0,0 +1,144 @@
+use core::time::Duration;
+
+use super::{Error, Mode, Table, TemperatureMatch, TemperatureRange, Transition};
+
+/// How long an update takes, as returned by [`Table::estimate`] and
+/// [`Table::estimate_histogram`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Estimate {
+    /// Frames up to the last one driving one of the pixels of the update.
+    pub frames: usize,
+
+    /// Frames of the whole waveform, which is what the update takes when
+    /// every frame of the waveform is sent to the panel.
+    pub waveform_frames: usize,
+
+    /// Frame rate the durations are computed at, in Hz.
+    pub frame_rate: u8,
+
+    /// Frame rate of older controllers from the header, when it is set and
+    /// differs from `frame_rate`.
+    pub old_frame_rate: Option<u8>,
+
+    /// The temperature range of the waveform used.
+    pub range: TemperatureRange,
+
+    /// Whether the temperature was inside of `range` or had to be clamped.
+    pub temperature: TemperatureMatch,
+}
+
+fn duration(frames: usize, frame_rate: u8) -> Duration {
+    Duration::from_nanos(frames as u64 * 1_000_000_000 / frame_rate as u64)
+}
+
+impl Estimate {
+    /// Time taken by the frames driving the pixels.
+    pub fn duration(&self) -> Duration {
+        duration(self.frames, self.frame_rate)
+    }
+
+    /// Time taken by the whole waveform.
+    pub fn waveform_duration(&self) -> Duration {
+        duration(self.waveform_frames, self.frame_rate)
+    }
+
+    /// Time taken by the frames driving the pixels on older controllers.
+    pub fn old_duration(&self) -> Option<Duration> {
+        self.old_frame_rate
+            .map(|frame_rate| duration(self.frames, frame_rate))
+    }
+}
+
+impl Table<'_> {
+    /// Estimate how long updating pixels going through `transition` takes
+    /// in `mode` at `temperature`.
+    ///
+    /// # Panics
+    ///
+    /// If `transition` is out of bounds for the bit depth of the table.
+    pub fn estimate(
+        &self,
+        mode: Mode,
+        temperature: u8,
+        transition: Transition,
+    ) -> Result<Estimate, Error> {
+        self.estimate_histogram(mode, temperature, [(transition, 1)])
+    }
+
+    /// Estimate how long an update takes in `mode` at `temperature`, given
+    /// how many pixels go through each transition.
+    ///
+    /// Transitions with a count of 0 are ignored, an update with no pixels
+    /// taking no frames.
+    ///
+    /// # Panics
+    ///
+    /// If a transition is out of bounds for the bit depth of the table.
+    pub fn estimate_histogram<I>(
+        &self,
+        mode: Mode,
+        temperature: u8,
+        histogram: I,
+    ) -> Result<Estimate, Error>
+    where
+        I: IntoIterator<Item = (Transition, usize)>,
+    {
+        let lookup = self.lookup(mode, temperature)?;
+
+        let frames = histogram
+            .into_iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(transition, _)| lookup.transition(transition).active_len())
+            .max()
+            .unwrap_or(0);
+
+        let frame_rate = self.frame_rate;
+        let old_frame_rate =
+            Some(self.info.old_frame_rate).filter(|rate| *rate != 0 && *rate != frame_rate);
+
+        Ok(Estimate {
+            frames,
+            waveform_frames: lookup.frame_count(),
+            frame_rate,
+            old_frame_rate,
+            range: lookup.range,
+            temperature: lookup.temperature,
+        })
+    }
+}
+
+#[test]
+fn estimate_test() {
+    use super::{Phase, PhaseMatrix, WaveformInfo};
+
+    let mut frames = vec![PhaseMatrix::new(); 10];
+    frames[1].set(5, 0, Phase::Black);
+    frames[3].set(0, 5, Phase::White);
+
+    let info = WaveformInfo {
+        frame_rate: 100,
+        old_frame_rate: 50,
+        ..WaveformInfo::example()
+    };
+    let table = Table::new(info, vec![0, 50], vec![vec![frames]]);
+
+    let estimate = table
+        .estimate(Mode::INIT, 20, Transition { from: 0, to: 5 })
+        .unwrap();
+    assert_eq!(estimate.frames, 2);
+    assert_eq!(estimate.waveform_frames, 10);
+    assert_eq!(estimate.duration(), Duration::from_millis(20));
+    assert_eq!(estimate.waveform_duration(), Duration::from_millis(100));
+    assert_eq!(estimate.old_duration(), Some(Duration::from_millis(40)));
+
+    let histogram = [
+        (Transition { from: 0, to: 5 }, 100),
+        (Transition { from: 5, to: 0 }, 3),
+        (Transition { from: 7, to: 9 }, 0),
+    ];
+    let estimate = table.estimate_histogram(Mode::INIT, 20, histogram).unwrap();
+    assert_eq!(estimate.frames, 4);
+
+    let estimate = table.estimate_histogram(Mode::INIT, 20, []).unwrap();
+    assert_eq!(estimate.duration(), Duration::ZERO);
+}
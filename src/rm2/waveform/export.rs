@@ -0,0 +1,435 @@
+//! Exporting tables to JSON and CSV, for looking at waveforms with other
+//! tools, and importing them back from JSON.
+
+use std::io::{BufWriter, Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    BitDepth, Error, ErrorKind, Phase, PhaseMatrix, Table, Transition, Waveform, WaveformInfo,
+};
+
+/// Version of the JSON format written by [`Table::write_json`].
+const VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Document {
+    version: u32,
+    info: Info,
+    bit_depth: u8,
+    temperatures: Vec<u8>,
+    modes: Vec<ModeWaveforms>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Info {
+    serial: u32,
+    run_type: u8,
+    fpl_platform: u8,
+    fpl_lot: u16,
+    adhesive_run: u8,
+    waveform_version: u8,
+    waveform_subversion: u8,
+    waveform_type: u8,
+    fpl_size: u8,
+    mfg_code: u8,
+    waveform_revision: u8,
+    old_frame_rate: u8,
+    frame_rate: u8,
+    vcom_offset: u8,
+    fvsn: u8,
+    luts: u8,
+    advanced_wfm_flags: u8,
+    eb: u8,
+    sb: u8,
+    filename: String,
+    extra_info: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ModeWaveforms {
+    name: String,
+    waveforms: Vec<RangeWaveform>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RangeWaveform {
+    temperatures: [u8; 2],
+    frames: Vec<Vec<Vec<u8>>>,
+}
+
+impl Info {
+    fn new(info: &WaveformInfo) -> Self {
+        Info {
+            serial: info.serial,
+            run_type: info.run_type.into(),
+            fpl_platform: info.fpl_platform,
+            fpl_lot: info.fpl_lot,
+            adhesive_run: info.adhesive_run,
+            waveform_version: info.waveform_version,
+            waveform_subversion: info.waveform_subversion,
+            waveform_type: info.waveform_type,
+            fpl_size: info.fpl_size.into(),
+            mfg_code: info.manufacturer.code(),
+            waveform_revision: info.waveform_revision,
+            old_frame_rate: info.old_frame_rate,
+            frame_rate: info.frame_rate,
+            vcom_offset: info.vcom_offset,
+            fvsn: info.fvsn,
+            luts: info.luts,
+            advanced_wfm_flags: info.advanced_wfm_flags,
+            eb: info.eb,
+            sb: info.sb,
+            filename: String::from_utf8_lossy(&info.filename).into_owned(),
+            extra_info: info
+                .extra_info
+                .as_ref()
+                .map(|bytes| String::from_utf8_lossy(bytes).into_owned()),
+        }
+    }
+
+    /// The info of a table with `mode_count` modes and `temp_range_count`
+    /// temperature ranges, leaving the fields that are only known once
+    /// the table is written at 0.
+    fn into_waveform_info(self, mode_count: usize, temp_range_count: usize) -> WaveformInfo {
+        WaveformInfo {
+            checksum: 0,
+            filesize: 0,
+            serial: self.serial,
+            run_type: self.run_type.into(),
+            fpl_platform: self.fpl_platform,
+            fpl_lot: self.fpl_lot,
+            adhesive_run: self.adhesive_run,
+            waveform_version: self.waveform_version,
+            waveform_subversion: self.waveform_subversion,
+            waveform_type: self.waveform_type,
+            fpl_size: self.fpl_size.into(),
+            manufacturer: self.mfg_code.into(),
+            waveform_revision: self.waveform_revision,
+            old_frame_rate: self.old_frame_rate,
+            frame_rate: self.frame_rate,
+            vcom_offset: self.vcom_offset,
+            extra_info_addr: 0,
+            checksum1: 0,
+            wmta: 0,
+            fvsn: self.fvsn,
+            luts: self.luts,
+            mode_count: mode_count.saturating_sub(1) as u8,
+            temp_range_count: temp_range_count.saturating_sub(1) as u8,
+            advanced_wfm_flags: self.advanced_wfm_flags,
+            eb: self.eb,
+            sb: self.sb,
+            checksum2: 0,
+            filename: self.filename.into_bytes(),
+            extra_info: self.extra_info.map(String::into_bytes),
+        }
+    }
+}
+
+/// The phases of a frame, indexed by source then target intensity.
+fn frame_phases(matrix: &PhaseMatrix, levels: usize) -> Vec<Vec<u8>> {
+    (0..levels as u8)
+        .map(|from| {
+            (0..levels as u8)
+                .map(|to| matrix.phase(Transition { from, to }) as u8)
+                .collect()
+        })
+        .collect()
+}
+
+fn phase(value: u8) -> Option<Phase> {
+    match value {
+        0 => Some(Phase::Noop),
+        1 => Some(Phase::Black),
+        2 => Some(Phase::White),
+        _ => None,
+    }
+}
+
+fn frame_matrix(phases: &[Vec<u8>], levels: usize, location: &str) -> Result<PhaseMatrix, Error> {
+    let invalid = |message: String| ErrorKind::InvalidLayout(format!("{location}: {message}"));
+
+    if phases.len() != levels {
+        return Err(invalid(format!(
+            "{} source intensities instead of {levels}",
+            phases.len()
+        ))
+        .into());
+    }
+
+    let mut matrix = PhaseMatrix::new();
+
+    for (from, targets) in phases.iter().enumerate() {
+        if targets.len() != levels {
+            return Err(invalid(format!(
+                "{} target intensities from {from} instead of {levels}",
+                targets.len()
+            ))
+            .into());
+        }
+
+        for (to, value) in targets.iter().enumerate() {
+            let phase = phase(*value)
+                .ok_or_else(|| invalid(format!("invalid phase {value} from {from} to {to}")))?;
+            matrix.set(to, from, phase);
+        }
+    }
+
+    Ok(matrix)
+}
+
+impl Table<'_> {
+    /// Write every waveform of the table to `output` as JSON.
+    ///
+    /// The JSON document is an object with the following fields:
+    ///
+    /// - `version`: version of the format, currently `1`.
+    /// - `info`: the header fields of [`WaveformInfo`] that don't describe the
+    ///   layout of the file, with their raw values: `serial`, `run_type`,
+    ///   `fpl_platform`, `fpl_lot`, `adhesive_run`, `waveform_version`,
+    ///   `waveform_subversion`, `waveform_type`, `fpl_size`, `mfg_code`,
+    ///   `waveform_revision`, `old_frame_rate`, `frame_rate`, `vcom_offset`,
+    ///   `fvsn`, `luts`, `advanced_wfm_flags`, `eb` and `sb` are numbers, while
+    ///   `filename` and `extra_info` are strings, `extra_info` being `null` when
+    ///   the file has no extra information block.
+    /// - `bit_depth`: `4` or `5`, which makes the frames 16 or 32 gray levels wide.
+    /// - `temperatures`: the boundaries of the temperature ranges in Celsius.
+    /// - `modes`: one object per mode, in the order of the mode table, with:
+    ///   - `name`: the name of the mode, like `GC16`, or `modeN` for the mode at
+    ///     index `N` when it has no known name.
+    ///   - `waveforms`: one object per temperature range, from coldest to
+    ///     warmest, with:
+    ///     - `temperatures`: the `[min, max)` range the waveform is meant for.
+    ///     - `frames`: the frames of the waveform, each frame being an array
+    ///       indexed by the intensity a pixel comes from of arrays indexed by the
+    ///       intensity it goes to, so the phase of a transition in a frame is at
+    ///       `frames[frame][from][to]`. Phases are `0` for no-op, `1` for black
+    ///       and `2` for white.
+    ///
+    /// Mode names and temperature ranges are only there to make the document
+    /// easier to read and are ignored when importing it: modes are recognized by
+    /// their position like in .wbf files. Waveforms shared between modes or
+    /// temperature ranges are written out every time and stored once again when
+    /// importing.
+    pub fn write_json<W: Write>(&self, output: &mut W) -> Result<(), Error> {
+        let levels = self.gray_levels();
+        let mut modes = Vec::with_capacity(self.modes().len());
+
+        for (mode_index, mode) in self.modes().iter().enumerate() {
+            let waveforms = self
+                .temperature_ranges()
+                .enumerate()
+                .map(|(range_index, range)| {
+                    let waveform = self.waveform(mode_index, range_index)?;
+
+                    Ok(RangeWaveform {
+                        temperatures: [range.min, range.max],
+                        frames: waveform
+                            .iter()
+                            .map(|matrix| frame_phases(matrix, levels))
+                            .collect(),
+                    })
+                })
+                .collect::<Result<_, Error>>()?;
+
+            modes.push(ModeWaveforms {
+                name: mode.to_string(),
+                waveforms,
+            });
+        }
+
+        let document = Document {
+            version: VERSION,
+            info: Info::new(&self.info),
+            bit_depth: self.bit_depth.bits(),
+            temperatures: self.temperatures.clone(),
+            modes,
+        };
+
+        let mut output = BufWriter::new(output);
+        serde_json::to_writer(&mut output, &document).map_err(ErrorKind::Json)?;
+        output.flush()?;
+
+        Ok(())
+    }
+
+    /// Write every phase of every waveform of the table to `output` as CSV.
+    ///
+    /// The CSV file has one row per phase of every frame, with the columns
+    /// `mode,temperature_min,temperature_max,frame,from,to,phase` using the same
+    /// values as the JSON document.
+    pub fn write_csv<W: Write>(&self, output: &mut W) -> Result<(), Error> {
+        let levels = self.gray_levels() as u8;
+        let mut output = BufWriter::new(output);
+
+        writeln!(
+            output,
+            "mode,temperature_min,temperature_max,frame,from,to,phase"
+        )?;
+
+        for (mode_index, mode) in self.modes().iter().enumerate() {
+            for (range_index, range) in self.temperature_ranges().enumerate() {
+                let waveform = self.waveform(mode_index, range_index)?;
+
+                for (frame, matrix) in waveform.iter().enumerate() {
+                    for from in 0..levels {
+                        for to in 0..levels {
+                            let phase = matrix.phase(Transition { from, to }) as u8;
+                            writeln!(
+                                output,
+                                "{mode},{},{},{frame},{from},{to},{phase}",
+                                range.min, range.max
+                            )?;
+                        }
+                    }
+                }
+            }
+        }
+
+        output.flush()?;
+
+        Ok(())
+    }
+}
+
+impl Table<'static> {
+    /// Read a table from the JSON written by [`Table::write_json`].
+    pub fn from_json<R: Read>(input: R) -> Result<Self, Error> {
+        let document: Document = serde_json::from_reader(input).map_err(ErrorKind::Json)?;
+
+        if document.version != VERSION {
+            return Err(ErrorKind::InvalidLayout(format!(
+                "unsupported version {}",
+                document.version
+            ))
+            .into());
+        }
+
+        let bit_depth = match document.bit_depth {
+            4 => BitDepth::Four,
+            5 => BitDepth::Five,
+            bits => {
+                return Err(
+                    ErrorKind::InvalidLayout(format!("unsupported bit depth {bits}")).into(),
+                );
+            }
+        };
+        if BitDepth::from_luts(document.info.luts) != bit_depth {
+            return Err(ErrorKind::InvalidLayout(format!(
+                "bit_depth {} doesn't match luts 0x{:x}",
+                document.bit_depth, document.info.luts
+            ))
+            .into());
+        }
+
+        let levels = bit_depth.gray_levels();
+        let temp_range_count = document.temperatures.len().saturating_sub(1);
+
+        let waveforms = document
+            .modes
+            .iter()
+            .enumerate()
+            .map(|(mode_index, mode)| {
+                if mode.waveforms.len() != temp_range_count {
+                    return Err(ErrorKind::InvalidLayout(format!(
+                        "modes[{mode_index}] has {} waveforms for {temp_range_count} temperature ranges",
+                        mode.waveforms.len()
+                    ))
+                    .into());
+                }
+
+                mode.waveforms
+                    .iter()
+                    .enumerate()
+                    .map(|(range_index, waveform)| {
+                        waveform
+                            .frames
+                            .iter()
+                            .enumerate()
+                            .map(|(frame, phases)| {
+                                let location =
+                                    format!("modes[{mode_index}].waveforms[{range_index}].frames[{frame}]");
+                                frame_matrix(phases, levels, &location)
+                            })
+                            .collect::<Result<Waveform, Error>>()
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let info = document
+            .info
+            .into_waveform_info(waveforms.len(), temp_range_count);
+
+        Ok(Table::new(info, document.temperatures, waveforms))
+    }
+}
+
+#[test]
+fn json_round_trip_test() {
+    use super::Mode;
+
+    let mut matrix = PhaseMatrix::new();
+    matrix.set(3, 7, Phase::Black);
+    matrix.set(7, 3, Phase::White);
+    let waveform = vec![PhaseMatrix::new(), matrix.clone(), matrix];
+
+    let waveforms = vec![
+        vec![waveform.clone(), waveform.clone()],
+        vec![waveform.clone(), waveform[1..].to_vec()],
+    ];
+    let table = Table::new(WaveformInfo::example(), vec![0, 20, 50], waveforms);
+
+    let mut json = vec![];
+    table.write_json(&mut json).unwrap();
+    let imported = Table::from_json(json.as_slice()).unwrap();
+
+    assert_eq!(imported.bit_depth(), table.bit_depth());
+    assert_eq!(imported.temperatures, table.temperatures);
+    assert_eq!(
+        imported.info,
+        WaveformInfo {
+            mode_count: 1,
+            temp_range_count: 1,
+            ..WaveformInfo::example()
+        }
+    );
+    assert_eq!(imported.blocks.len(), 2);
+    for i in 0..2 {
+        for j in 0..2 {
+            assert_eq!(
+                imported.waveform(i, j).unwrap(),
+                table.waveform(i, j).unwrap()
+            );
+        }
+    }
+
+    let mut csv = vec![];
+    table.write_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let mut lines = csv.lines();
+
+    assert_eq!(
+        lines.next(),
+        Some("mode,temperature_min,temperature_max,frame,from,to,phase")
+    );
+    assert_eq!(lines.clone().count(), (3 + 3 + 3 + 2) * 32 * 32);
+    assert!(lines.any(|line| line == format!("{},0,20,1,3,7,2", Mode::INIT)));
+
+    let json = String::from_utf8(json).unwrap();
+
+    let mismatch = json.replacen("\"bit_depth\":5", "\"bit_depth\":4", 1);
+    assert_ne!(mismatch, json);
+    let error = Table::from_json(mismatch.as_bytes()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid table layout: bit_depth 4 doesn't match luts 0x4"
+    );
+
+    let invalid = json.replacen("[[[0,", "[[[3,", 1);
+    let error = Table::from_json(invalid.as_bytes()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid table layout: modes[0].waveforms[0].frames[0]: invalid phase 3 from 0 to 0"
+    );
+}
@@ -0,0 +1,312 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use super::Header;
+
+/// Represents the type of FPL runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunType {
+    Baseline,
+    Test,
+    Production,
+    Qualification,
+
+    /// A run type with no known meaning, kept as is.
+    Unknown(u8),
+}
+
+impl From<u8> for RunType {
+    fn from(value: u8) -> Self {
+        match value {
+            0x00 => RunType::Baseline,
+            0x01 => RunType::Test,
+            0x02 => RunType::Production,
+            0x03 => RunType::Qualification,
+            _ => RunType::Unknown(value),
+        }
+    }
+}
+
+impl From<RunType> for u8 {
+    fn from(value: RunType) -> Self {
+        match value {
+            RunType::Baseline => 0x00,
+            RunType::Test => 0x01,
+            RunType::Production => 0x02,
+            RunType::Qualification => 0x03,
+            RunType::Unknown(value) => value,
+        }
+    }
+}
+
+/// Size and resolution of the front plane laminate the waveform was tuned for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FplSize {
+    /// 5" 800x600
+    Inch5_800x600,
+
+    /// 6" 800x600
+    Inch6_800x600,
+
+    /// 6.1" 1024x768
+    Inch6_1_1024x768,
+
+    /// 6" 1024x768
+    Inch6_1024x768,
+
+    /// 8" 1024x768
+    Inch8_1024x768,
+
+    /// 9.7" 1200x825
+    Inch9_7_1200x825,
+
+    /// 9.7" 1600x1200
+    Inch9_7_1600x1200,
+
+    /// A size with no known meaning, kept as is.
+    Unknown(u8),
+}
+
+impl From<u8> for FplSize {
+    fn from(value: u8) -> Self {
+        match value {
+            0x00 => FplSize::Inch5_800x600,
+            0x01 => FplSize::Inch6_800x600,
+            0x02 => FplSize::Inch6_1_1024x768,
+            0x03 => FplSize::Inch6_1024x768,
+            0x10 => FplSize::Inch8_1024x768,
+            0x11 => FplSize::Inch9_7_1200x825,
+            0x12 => FplSize::Inch9_7_1600x1200,
+            _ => FplSize::Unknown(value),
+        }
+    }
+}
+
+impl From<FplSize> for u8 {
+    fn from(value: FplSize) -> Self {
+        match value {
+            FplSize::Inch5_800x600 => 0x00,
+            FplSize::Inch6_800x600 => 0x01,
+            FplSize::Inch6_1_1024x768 => 0x02,
+            FplSize::Inch6_1024x768 => 0x03,
+            FplSize::Inch8_1024x768 => 0x10,
+            FplSize::Inch9_7_1200x825 => 0x11,
+            FplSize::Inch9_7_1600x1200 => 0x12,
+            FplSize::Unknown(value) => value,
+        }
+    }
+}
+
+/// Manufacturer of the panel, decoded from the manufacturer code.
+///
+/// Each manufacturer uses a range of codes, the exact code is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Manufacturer {
+    /// PVI/E Ink Holdings, codes 0x33 to 0x3C.
+    Pvi(u8),
+
+    /// LG Display, codes 0xA0 to 0xAF.
+    Lgd(u8),
+
+    /// A code outside of the known ranges.
+    Unknown(u8),
+}
+
+impl Manufacturer {
+    /// The manufacturer code, as stored in the header.
+    pub fn code(&self) -> u8 {
+        match *self {
+            Manufacturer::Pvi(code) | Manufacturer::Lgd(code) | Manufacturer::Unknown(code) => code,
+        }
+    }
+}
+
+impl From<u8> for Manufacturer {
+    fn from(value: u8) -> Self {
+        match value {
+            0x33..=0x3C => Manufacturer::Pvi(value),
+            0xA0..=0xAF => Manufacturer::Lgd(value),
+            _ => Manufacturer::Unknown(value),
+        }
+    }
+}
+
+/// Metadata stored in the header of a .wbf file.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaveformInfo {
+    /// CRC32 of everything that follows it, from byte 4 to the end of the
+    /// file.
+    pub checksum: u32,
+
+    /// File length in bytes.
+    pub filesize: u32,
+
+    /// Unique value assigned to each waveform file.
+    pub serial: u32,
+
+    /// Kind of FPL run the waveform was tuned on.
+    pub run_type: RunType,
+
+    /// Platform of the front plane laminate, the family of panels the
+    /// waveform is for.
+    pub fpl_platform: u8,
+
+    /// FPL lot number.
+    pub fpl_lot: u16,
+
+    /// Adhesive run number of the FPL, which inkwave also reads as the
+    /// version of the mode layout. It tells which modes
+    /// [`Table::modes`](super::Table::modes) names.
+    pub adhesive_run: u8,
+
+    /// Version of the waveform.
+    pub waveform_version: u8,
+
+    /// Subversion of the waveform, within `waveform_version`.
+    pub waveform_subversion: u8,
+
+    /// Type of the waveform, which identifies the set of modes it was
+    /// generated for.
+    pub waveform_type: u8,
+
+    /// Size and resolution of the panel.
+    pub fpl_size: FplSize,
+
+    /// Manufacturer of the panel.
+    pub manufacturer: Manufacturer,
+
+    /// Revision of the tuning of the waveform, also called its bias.
+    pub waveform_revision: u8,
+
+    /// Frame rate used by older controllers, in Hz.
+    pub old_frame_rate: u8,
+
+    /// Frame rate in Hz, or 0 if the controller default (85 Hz) should be used.
+    pub frame_rate: u8,
+
+    /// Shift applied to the VCOM voltage of the panel, in the units of the
+    /// controller.
+    pub vcom_offset: u8,
+
+    /// Address of the extra waveform information block, or 0 if there is
+    /// none.
+    pub extra_info_addr: u32,
+
+    /// Additive checksum of bytes 4 to 30 of the header.
+    pub checksum1: u8,
+
+    /// Address of the waveform mode table.
+    pub wmta: u32,
+
+    /// Version of the file format.
+    pub fvsn: u8,
+
+    /// Layout of the lookup tables, whose bits 2 and 3 give the bit depth of
+    /// the waveforms (see [`BitDepth`](super::BitDepth)).
+    pub luts: u8,
+
+    /// Number of modes in the file minus one.
+    pub mode_count: u8,
+
+    /// Number of temperature ranges in the file minus one.
+    pub temp_range_count: u8,
+
+    /// Flags enabling the advanced waveform modes, 0 when the file has none.
+    pub advanced_wfm_flags: u8,
+
+    /// Byte 40 of the header, named `eb` by inkwave. Its meaning is unknown.
+    pub eb: u8,
+
+    /// Byte 41 of the header, named `sb` by inkwave. Its meaning is unknown.
+    pub sb: u8,
+
+    /// Additive checksum of bytes 32 to 46 of the header.
+    pub checksum2: u8,
+
+    /// Name of the file the waveform was generated from, stored after the
+    /// temperature ranges.
+    pub filename: Vec<u8>,
+
+    /// Contents of the extra waveform information block at `extra_info_addr`.
+    ///
+    /// It has the same layout as the filename block: a length byte, the
+    /// contents and an additive checksum.
+    pub extra_info: Option<Vec<u8>>,
+}
+
+#[cfg(feature = "alloc")]
+impl WaveformInfo {
+    pub(super) fn new(header: &Header, filename: Vec<u8>, extra_info: Option<Vec<u8>>) -> Self {
+        WaveformInfo {
+            checksum: header.checksum,
+            filesize: header.filesize,
+            serial: header.serial,
+            run_type: header.run_type.into(),
+            fpl_platform: header.fpl_platform,
+            fpl_lot: header.fpl_lot,
+            adhesive_run: header.adhesive_run,
+            waveform_version: header.waveform_version,
+            waveform_subversion: header.waveform_subversion,
+            waveform_type: header.waveform_type,
+            fpl_size: header.fpl_size.into(),
+            manufacturer: header.mfg_code.into(),
+            waveform_revision: header.waveform_revision,
+            old_frame_rate: header.old_frame_rate,
+            frame_rate: header.frame_rate,
+            vcom_offset: header.vcom_offset,
+            extra_info_addr: header.extra_info_addr,
+            checksum1: header.checksum1,
+            wmta: header.wmta,
+            fvsn: header.fvsn,
+            luts: header.luts,
+            mode_count: header.mode_count,
+            temp_range_count: header.temp_range_count,
+            advanced_wfm_flags: header.advanced_wfm_flags,
+            eb: header.eb,
+            sb: header.sb,
+            checksum2: header.checksum2,
+            filename,
+            extra_info,
+        }
+    }
+}
+
+#[cfg(all(feature = "alloc", test))]
+impl WaveformInfo {
+    /// Made up metadata for tests.
+    pub(crate) fn example() -> Self {
+        WaveformInfo {
+            checksum: 0,
+            filesize: 0,
+            serial: 0x12345678,
+            run_type: RunType::Production,
+            fpl_platform: 3,
+            fpl_lot: 0x1234,
+            adhesive_run: 0x19,
+            waveform_version: 1,
+            waveform_subversion: 2,
+            waveform_type: 0x15,
+            fpl_size: FplSize::Unknown(0x32),
+            manufacturer: Manufacturer::Pvi(0x33),
+            waveform_revision: 4,
+            old_frame_rate: 85,
+            frame_rate: 85,
+            vcom_offset: 0,
+            extra_info_addr: 0,
+            checksum1: 0,
+            wmta: 0,
+            fvsn: 1,
+            luts: 4,
+            mode_count: 0,
+            temp_range_count: 0,
+            advanced_wfm_flags: 3,
+            eb: 0,
+            sb: 0,
+            checksum2: 0,
+            filename: b"test.wbf".to_vec(),
+            extra_info: Some(b"test.wbf".to_vec()),
+        }
+    }
+}
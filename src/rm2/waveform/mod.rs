@@ -0,0 +1,1376 @@
+use core::{
+    fmt,
+    ops::{Index, Range},
+};
+
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::{fs, io, path::PathBuf};
+
+use crate::{
+    byte_reader::{Cursor, UnexpectedEof, u24_from_le_bytes},
+    rm2::checksum,
+};
+
+#[cfg(feature = "alloc")]
+mod diff;
+#[cfg(feature = "alloc")]
+mod estimate;
+#[cfg(feature = "serde")]
+mod export;
+mod info;
+#[cfg(feature = "std")]
+mod render;
+#[cfg(feature = "alloc")]
+mod table;
+mod temperature;
+mod transition;
+#[cfg(feature = "std")]
+mod write;
+
+pub use crate::byte_reader::{Field, FieldPath, Location, Segment};
+#[cfg(feature = "alloc")]
+pub use diff::{Diff, FieldChange, TransitionChange, WaveformChange};
+#[cfg(feature = "alloc")]
+pub use estimate::Estimate;
+#[cfg(feature = "alloc")]
+pub use info::WaveformInfo;
+pub use info::{FplSize, Manufacturer, RunType};
+#[cfg(feature = "std")]
+pub use render::Image;
+#[cfg(feature = "alloc")]
+pub use table::{Lookup, Table, Waveform};
+pub use temperature::{ClampingPolicy, TemperatureMatch, TemperatureRange};
+pub use transition::{Transition, TransitionPhases};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    /// CRC32 of everything that follows it, from byte 4 to the end of the
+    /// file.
+    pub checksum: u32,
+
+    /// File length in bytes
+    pub filesize: u32,
+
+    /// Unique value assigned to each waveform file.
+    pub serial: u32,
+
+    /// Represents the type of FPL runs.
+    pub run_type: u8,
+
+    pub fpl_platform: u8,
+
+    /// FPL lot number.
+    pub fpl_lot: u16,
+
+    /// Adhesive run number, also read as the version of the mode layout.
+    pub adhesive_run: u8,
+
+    pub waveform_version: u8,
+    pub waveform_subversion: u8,
+    pub waveform_type: u8,
+    pub fpl_size: u8,
+    pub mfg_code: u8,
+    pub waveform_revision: u8,
+    pub old_frame_rate: u8,
+    pub frame_rate: u8,
+    pub vcom_offset: u8,
+    pub extra_info_addr: u32,
+    pub checksum1: u8,
+    pub wmta: u32,
+    pub fvsn: u8,
+    pub luts: u8,
+    pub mode_count: u8,
+    pub temp_range_count: u8,
+    pub advanced_wfm_flags: u8,
+    pub eb: u8,
+    pub sb: u8,
+    pub checksum2: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    INIT,
+    DU,
+    GC16,
+    GC4,
+    GL16,
+    GLR16,
+    GLD16,
+    A2,
+    DU4,
+
+    /// A mode at this index of the mode table that has no known name.
+    Other(u8),
+}
+
+impl Mode {
+    /// The modes of a file with `count` modes, in the order of its mode
+    /// table. Modes the layout has no name for are [`Mode::Other`].
+    ///
+    /// Waveform files don't name their modes, so the layout is chosen from
+    /// the mode version stored in `adhesive_run` like inkwave does. Older
+    /// 4-bit panels have GC4 instead of the GL16 family, and the reMarkable
+    /// 2's version extends the 7 mode layout with DU4. Files of other
+    /// versions get the reMarkable 2's layout, which every file used before
+    /// the layout depended on the version.
+    fn layout(count: usize, mode_version: u8) -> impl ExactSizeIterator<Item = Mode> {
+        use Mode::*;
+
+        let names: &[Mode] = match mode_version {
+            0x10 => &[INIT, DU, GC16, GC4],
+            0x13 => &[INIT, DU, GC16, GC4, A2],
+            0x18 => &[INIT, DU, GC16, GL16, GLR16, GLD16, A2],
+            _ => &[INIT, DU, GC16, GL16, GLR16, GLD16, A2, DU4],
+        };
+
+        (0..count).map(move |i| names.get(i).copied().unwrap_or(Other(i as u8)))
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mode::Other(index) => write!(f, "mode{index}"),
+            mode => write!(f, "{mode:?}"),
+        }
+    }
+}
+
+/// What went wrong while reading or writing a waveform table.
+#[derive(Debug, thiserror::Error)]
+pub enum ErrorKind {
+    #[error("invalid checksum: expected 0x{expected:x}, actual 0x{actual:x}")]
+    InvalidChecksum { expected: u8, actual: u8 },
+
+    #[error("invalid CRC32: expected 0x{expected:08x}, actual 0x{actual:08x}")]
+    InvalidCrc32 { expected: u32, actual: u32 },
+
+    #[error("file size mismatch: header says {expected} bytes, file is {actual} bytes")]
+    SizeMismatch { expected: u32, actual: u64 },
+
+    #[error("invalid phase: 0b{0:08b}")]
+    InvalidPhase(u8),
+
+    #[error("pointer points outside of the file: 0x{0:x}")]
+    DanglingPointer(u32),
+
+    #[error("waveform block ends in the middle of a run or frame")]
+    TruncatedBlock,
+
+    #[error("run makes the waveform longer than {MAX_FRAMES} frames")]
+    OverlongRun,
+
+    #[error("unexpected end of file")]
+    UnexpectedEof,
+
+    #[error("mode {0:?} is not in the waveform table")]
+    ModeNotFound(Mode),
+
+    #[error("temperature {temperature}°C is outside of the waveform table ranges")]
+    TemperatureOutOfRange { temperature: u8 },
+
+    #[cfg(feature = "alloc")]
+    #[error("invalid table layout: {0}")]
+    InvalidLayout(String),
+
+    #[cfg(feature = "serde")]
+    #[error("invalid JSON: {0}")]
+    Json(#[source] serde_json::Error),
+
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Read(#[from] io::Error),
+}
+
+/// An [`ErrorKind`], along with the offset and field of the file it was
+/// found at when it comes from parsing.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    location: Option<Location>,
+}
+
+impl Error {
+    fn at(kind: ErrorKind, location: Location) -> Self {
+        Error {
+            kind,
+            location: Some(location),
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// Where in the file the error was found, like
+    /// `modes[3].temps[5].pointer at offset 0x1a2`.
+    pub fn location(&self) -> Option<&Location> {
+        self.location.as_ref()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "{location}: {}", self.kind),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        core::error::Error::source(&self.kind)
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error {
+            kind,
+            location: None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        ErrorKind::Read(error).into()
+    }
+}
+
+impl From<UnexpectedEof> for Error {
+    fn from(UnexpectedEof(location): UnexpectedEof) -> Self {
+        Error::at(ErrorKind::UnexpectedEof, location)
+    }
+}
+
+const HEADER_LENGTH: usize = 48;
+
+/// Bytes covered by `checksum1`: everything between the CRC32 and `checksum1`.
+const CHECKSUM1_RANGE: Range<usize> = 4..31;
+
+/// Bytes covered by `checksum2`: everything between `checksum1` and `checksum2`.
+const CHECKSUM2_RANGE: Range<usize> = 32..47;
+
+/// Read an additive checksum and check it against `bytes`.
+fn verify_checksum(bytes: &[u8], input: &mut Cursor<'_>) -> Result<u8, Error> {
+    let offset = input.offset();
+    let expected = input.u8()?;
+
+    let actual = checksum::additive_checksum(bytes.iter());
+    if actual != expected {
+        return Err(Error::at(
+            ErrorKind::InvalidChecksum { expected, actual },
+            input.location_at(offset),
+        ));
+    }
+
+    Ok(expected)
+}
+
+fn header(input: &mut Cursor<'_>) -> Result<Header, Error> {
+    input.field(Field::Header, |input| {
+        let start = input.offset();
+        let bytes = input.take(HEADER_LENGTH)?;
+        input.seek(start);
+
+        let checksum = input.field(Field::Checksum, |input| input.le_u32())?;
+        let filesize = input.field(Field::Filesize, |input| {
+            let filesize = input.le_u32()?;
+            let actual = input.bytes().len() as u64;
+
+            if actual != filesize as u64 {
+                return Err(Error::at(
+                    ErrorKind::SizeMismatch {
+                        expected: filesize,
+                        actual,
+                    },
+                    input.location_at(input.offset() - 4),
+                ));
+            }
+
+            Ok(filesize)
+        })?;
+        let serial = input.le_u32()?;
+        let run_type = input.u8()?;
+        let fpl_platform = input.u8()?;
+        let fpl_lot = input.le_u16()?;
+        let adhesive_run = input.u8()?;
+        let waveform_version = input.u8()?;
+        let waveform_subversion = input.u8()?;
+        let waveform_type = input.u8()?;
+        let fpl_size = input.u8()?;
+        let mfg_code = input.u8()?;
+        let waveform_revision = input.u8()?;
+        let old_frame_rate = input.u8()?;
+        let frame_rate = input.u8()?;
+        let vcom_offset = input.u8()?;
+        input.skip(2)?; // reserved
+        let extra_info_addr = input.field(Field::ExtraInfoAddr, |input| {
+            header_pointer(filesize, input)
+        })?;
+        let checksum1 = input.field(Field::Checksum1, |input| {
+            verify_checksum(&bytes[CHECKSUM1_RANGE], input)
+        })?;
+        let wmta = input.field(Field::Wmta, |input| header_pointer(filesize, input))?;
+        let fvsn = input.u8()?;
+        let luts = input.u8()?;
+        let mode_count = input.u8()?;
+        let temp_range_count = input.u8()?;
+        let advanced_wfm_flags = input.u8()?;
+        let eb = input.u8()?;
+        let sb = input.u8()?;
+        input.skip(5)?; // reserved
+        let checksum2 = input.field(Field::Checksum2, |input| {
+            verify_checksum(&bytes[CHECKSUM2_RANGE], input)
+        })?;
+
+        Ok(Header {
+            checksum,
+            filesize,
+            serial,
+            run_type,
+            fpl_platform,
+            fpl_lot,
+            adhesive_run,
+            waveform_version,
+            waveform_subversion,
+            waveform_type,
+            fpl_size,
+            mfg_code,
+            waveform_revision,
+            old_frame_rate,
+            frame_rate,
+            vcom_offset,
+            extra_info_addr,
+            checksum1,
+            wmta,
+            fvsn,
+            luts,
+            mode_count,
+            temp_range_count,
+            advanced_wfm_flags,
+            eb,
+            sb,
+            checksum2,
+        })
+    })
+}
+
+/// Read an address of the header and check that it points inside of the
+/// file.
+fn header_pointer(filesize: u32, input: &mut Cursor<'_>) -> Result<u32, Error> {
+    let offset = input.offset();
+    let pointer = input.le_u24()?;
+
+    if pointer >= filesize {
+        return Err(Error::at(
+            ErrorKind::DanglingPointer(pointer),
+            input.location_at(offset),
+        ));
+    }
+
+    Ok(pointer)
+}
+
+/// Verify the CRC32 in the header, which is calculated on everything that
+/// follows the checksum field itself.
+fn verify_crc32(header: &Header, input: &mut Cursor<'_>) -> Result<(), Error> {
+    let mut crc = checksum::Crc32::new();
+    crc.update(input.bytes().get(4..).unwrap_or_default());
+
+    let actual = crc.finish();
+    if actual != header.checksum {
+        let kind = ErrorKind::InvalidCrc32 {
+            expected: header.checksum,
+            actual,
+        };
+
+        input.seek(0);
+        return input.field(Field::Header, |input| {
+            input.field(Field::Checksum, |input| {
+                Err(Error::at(kind, input.location()))
+            })
+        });
+    }
+
+    Ok(())
+}
+
+fn pointer(input: &mut Cursor<'_>) -> Result<u32, Error> {
+    let pointer = input.take_const()?;
+    input.field(Field::Checksum, |input| verify_checksum(&pointer, input))?;
+
+    Ok(u24_from_le_bytes(pointer))
+}
+
+fn temperatures<'a>(count: usize, input: &mut Cursor<'a>) -> Result<&'a [u8], Error> {
+    input.field(Field::Temperatures, |input| {
+        let temperatures = input.take(count + 2)?;
+        input.field(Field::Checksum, |input| {
+            verify_checksum(temperatures, input)
+        })?;
+
+        Ok(temperatures)
+    })
+}
+
+/// A length-prefixed block of bytes followed by its checksum.
+fn sized_block<'a>(input: &mut Cursor<'a>) -> Result<&'a [u8], Error> {
+    let start = input.offset();
+    let len = input.u8()?;
+    input.seek(start);
+
+    // The checksum covers the length byte as well.
+    let block = input.take(len as usize + 1)?;
+    input.field(Field::Checksum, |input| verify_checksum(block, input))?;
+
+    Ok(&block[1..])
+}
+
+/// The filename block, which sits between the temperatures and the mode
+/// table. Files whose mode table follows the temperatures have none.
+fn filename<'a>(header: &Header, input: &mut Cursor<'a>) -> Result<&'a [u8], Error> {
+    if input.offset() >= header.wmta as usize {
+        return Ok(&[]);
+    }
+
+    input.field(Field::Filename, sized_block)
+}
+
+/// The extra waveform information block pointed to by `extra_info_addr`,
+/// if the file has one.
+fn extra_info<'a>(header: &Header, input: &mut Cursor<'a>) -> Result<Option<&'a [u8]>, Error> {
+    if header.extra_info_addr == 0 {
+        return Ok(None);
+    }
+
+    let position = input.offset();
+    input.seek(header.extra_info_addr as usize);
+    let extra_info = input.field(Field::ExtraInfo, sized_block)?;
+    input.seek(position);
+
+    Ok(Some(extra_info))
+}
+
+/// Read a pointer and check that it points inside of the file.
+fn checked_pointer(filesize: u32, input: &mut Cursor<'_>) -> Result<u32, Error> {
+    input.field(Field::Pointer, |input| {
+        let offset = input.offset();
+        let pointer = pointer(input)?;
+
+        if pointer >= filesize {
+            return Err(Error::at(
+                ErrorKind::DanglingPointer(pointer),
+                input.location_at(offset),
+            ));
+        }
+
+        Ok(pointer)
+    })
+}
+
+/// Run `f` with the field path of the waveform block of a mode and
+/// temperature range, like `modes[3].temps[5].waveform`.
+fn in_waveform<'a, T>(
+    input: &mut Cursor<'a>,
+    mode_index: usize,
+    range_index: usize,
+    f: impl FnOnce(&mut Cursor<'a>) -> Result<T, Error>,
+) -> Result<T, Error> {
+    input.field(Field::Modes, |input| {
+        input.index(mode_index, |input| {
+            input.field(Field::Temps, |input| {
+                input.index(range_index, |input| input.field(Field::Waveform, f))
+            })
+        })
+    })
+}
+
+/// Find the mode and temperature range to use for a lookup, returning their
+/// indices along with the range and how the temperature matched it.
+fn find_waveform(
+    mut modes: impl Iterator<Item = Mode>,
+    ranges: impl ExactSizeIterator<Item = TemperatureRange>,
+    mode: Mode,
+    temperature: u8,
+    clamping: ClampingPolicy,
+) -> Result<(usize, usize, TemperatureRange, TemperatureMatch), Error> {
+    let mode_index = modes
+        .position(|m| m == mode)
+        .ok_or(ErrorKind::ModeNotFound(mode))?;
+
+    let (range_index, range, matched) = temperature::find_range(ranges, temperature)
+        .ok_or(ErrorKind::TemperatureOutOfRange { temperature })?;
+
+    if matched != TemperatureMatch::InRange && clamping == ClampingPolicy::Reject {
+        return Err(ErrorKind::TemperatureOutOfRange { temperature }.into());
+    }
+
+    Ok((mode_index, range_index, range, matched))
+}
+
+/// A .wbf file borrowed from memory.
+///
+/// Only the header and the tables are read when parsing, and waveforms are
+/// decoded frame by frame straight from the borrowed bytes, so it works
+/// without an allocator.
+#[derive(Debug, Clone)]
+pub struct WaveformFile<'a> {
+    bytes: &'a [u8],
+    header: Header,
+    temperatures: &'a [u8],
+    filename: &'a [u8],
+    extra_info: Option<&'a [u8]>,
+
+    /// Offset of the table of pointers to the temperature table of each mode,
+    /// from `wmta`.
+    mode_table: usize,
+
+    bit_depth: BitDepth,
+
+    /// Every waveform pointer of the file, sorted and deduplicated, to find
+    /// where a block ends. Without an allocator, the pointers are scanned
+    /// again for every lookup instead.
+    #[cfg(feature = "alloc")]
+    block_starts: Vec<u32>,
+}
+
+/// The frames found for a mode and temperature by [`WaveformFile::lookup`].
+#[derive(Debug, Clone)]
+pub struct FrameLookup<'a> {
+    pub frames: Frames<'a>,
+
+    /// The temperature range the waveform is meant for.
+    pub range: TemperatureRange,
+
+    /// Whether the temperature was inside of `range` or had to be clamped.
+    pub temperature: TemperatureMatch,
+}
+
+impl<'a> WaveformFile<'a> {
+    pub fn parse(bytes: &'a [u8], options: &ParseOptions) -> Result<Self, Error> {
+        let mut input = Cursor::new(bytes);
+
+        let header = header(&mut input)?;
+        if options.verify_crc32 {
+            verify_crc32(&header, &mut input)?;
+        }
+        let temperatures = temperatures(header.temp_range_count as usize, &mut input)?;
+        let filename = filename(&header, &mut input)?;
+        let extra_info = extra_info(&header, &mut input)?;
+
+        #[cfg_attr(not(feature = "alloc"), expect(unused_mut))]
+        let mut file = WaveformFile {
+            bytes,
+            header,
+            temperatures,
+            filename,
+            extra_info,
+            mode_table: header.wmta as usize,
+            bit_depth: options
+                .bit_depth
+                .unwrap_or(BitDepth::from_luts(header.luts)),
+            #[cfg(feature = "alloc")]
+            block_starts: Vec::new(),
+        };
+
+        // Check every pointer up front, so that only corrupted waveform
+        // blocks can make a lookup fail.
+        #[cfg(feature = "alloc")]
+        {
+            let mut block_starts = file.pointers().collect::<Result<Vec<_>, _>>()?;
+            block_starts.sort_unstable();
+            block_starts.dedup();
+            file.block_starts = block_starts;
+        }
+        #[cfg(not(feature = "alloc"))]
+        file.pointers().try_for_each(|pointer| pointer.map(drop))?;
+
+        Ok(file)
+    }
+
+    /// The modes contained in the file, named after the layout of its mode
+    /// version. Modes the layout has no name for are [`Mode::Other`].
+    pub fn modes(&self) -> impl ExactSizeIterator<Item = Mode> + use<> {
+        Mode::layout(
+            self.header.mode_count as usize + 1,
+            self.header.adhesive_run,
+        )
+    }
+
+    /// The temperature ranges covered by the file, from coldest to warmest.
+    pub fn temperature_ranges(&self) -> impl ExactSizeIterator<Item = TemperatureRange> + 'a {
+        temperature::ranges(self.temperatures)
+    }
+
+    /// Name of the file the waveform was generated from.
+    pub fn filename(&self) -> &'a [u8] {
+        self.filename
+    }
+
+    /// Contents of the extra waveform information block, if any.
+    pub fn extra_info(&self) -> Option<&'a [u8]> {
+        self.extra_info
+    }
+
+    /// Bit depth the waveforms are decoded with, from the header unless
+    /// [`ParseOptions::bit_depth`] overrides it.
+    pub fn bit_depth(&self) -> BitDepth {
+        self.bit_depth
+    }
+
+    /// Frame rate to play the waveforms at, in Hz.
+    pub fn frame_rate(&self) -> u8 {
+        effective_frame_rate(self.header.frame_rate)
+    }
+
+    /// The pointers to the waveform blocks of every mode and temperature
+    /// range.
+    fn pointers(&self) -> impl Iterator<Item = Result<u32, Error>> + '_ {
+        let range_count = self.temperature_ranges().len();
+        (0..self.modes().len()).flat_map(move |mode_index| {
+            (0..range_count).map(move |range_index| self.pointer(mode_index, range_index))
+        })
+    }
+
+    /// The pointer to the waveform block of the mode and temperature range
+    /// at the given indices.
+    fn pointer(&self, mode_index: usize, range_index: usize) -> Result<u32, Error> {
+        let filesize = self.header.filesize;
+        let mut input = Cursor::new(self.bytes);
+
+        input.field(Field::Modes, |input| {
+            input.index(mode_index, |input| {
+                input.seek(self.mode_table + mode_index * 4);
+                let temps = checked_pointer(filesize, input)?;
+
+                input.seek(temps as usize + range_index * 4);
+                input.field(Field::Temps, |input| {
+                    input.index(range_index, |input| checked_pointer(filesize, input))
+                })
+            })
+        })
+    }
+
+    /// Length of the waveform block at `pointer`, assuming that it ends
+    /// where the next one starts.
+    #[cfg(feature = "alloc")]
+    fn block_length(&self, pointer: u32) -> Result<usize, Error> {
+        let next = self.block_starts.partition_point(|start| *start <= pointer);
+        let end = self
+            .block_starts
+            .get(next)
+            .copied()
+            .unwrap_or(self.header.filesize);
+
+        Ok((end - pointer) as usize)
+    }
+
+    /// Length of the waveform block at `pointer`, assuming that it ends
+    /// where the next one starts.
+    #[cfg(not(feature = "alloc"))]
+    fn block_length(&self, pointer: u32) -> Result<usize, Error> {
+        let mut end = self.header.filesize;
+
+        for next in self.pointers() {
+            let next = next?;
+            if next > pointer && next < end {
+                end = next;
+            }
+        }
+
+        Ok((end - pointer) as usize)
+    }
+
+    /// Find the waveform for a mode at the given temperature.
+    pub fn lookup(
+        &self,
+        mode: Mode,
+        temperature: u8,
+        clamping: ClampingPolicy,
+    ) -> Result<FrameLookup<'a>, Error> {
+        let (mode_index, range_index, range, matched) = find_waveform(
+            self.modes(),
+            self.temperature_ranges(),
+            mode,
+            temperature,
+            clamping,
+        )?;
+
+        let pointer = self.pointer(mode_index, range_index)?;
+        let length = self.block_length(pointer)?;
+
+        let mut input = Cursor::new(self.bytes);
+        input.seek(pointer as usize);
+
+        let frames = in_waveform(&mut input, mode_index, range_index, |input| {
+            Ok(Frames::new(input.clone(), length, self.bit_depth))
+        })?;
+
+        Ok(FrameLookup {
+            frames,
+            range,
+            temperature: matched,
+        })
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Noop = 0b00,
+    Black = 0b01,
+    White = 0b10,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PhaseCell(u8);
+
+impl PhaseCell {
+    pub fn new(phases: u8) -> Result<Self, ErrorKind> {
+        if phases & 0b11000000 == 0b11000000
+            || phases & 0b00110000 == 0b00110000
+            || phases & 0b00001100 == 0b00001100
+            || phases & 0b00000011 == 0b00000011
+        {
+            Err(ErrorKind::InvalidPhase(phases))
+        } else {
+            Ok(PhaseCell(phases))
+        }
+    }
+}
+
+impl PhaseCell {
+    fn phase(&self, index: u8) -> &'static Phase {
+        let shift = 6 - (2 * (index & 0b11));
+
+        match (self.0 >> shift) & 0b11 {
+            0b00 => &Phase::Noop,
+            0b01 => &Phase::Black,
+            0b10 => &Phase::White,
+            phase => unreachable!("invalid phase: 0b{phase:02b}"),
+        }
+    }
+}
+
+/// Largest number of intensities a waveform can have, for 5-bit files.
+pub const INTENSITY_VALUES: usize = 1 << 5;
+
+/// Number of phase cells used to store one column of a [`PhaseMatrix`].
+const CELLS_PER_COLUMN: usize = INTENSITY_VALUES / 4;
+
+/// Number of bits used to store the intensity of a pixel, which sets the
+/// size of the phase matrices of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitDepth {
+    /// 16 gray levels.
+    Four,
+
+    /// 32 gray levels, like the reMarkable 2.
+    Five,
+}
+
+impl BitDepth {
+    /// The bit depth of a file, from the `luts` field of its header like
+    /// inkwave does.
+    fn from_luts(luts: u8) -> Self {
+        if luts & 0x0C == 0x04 {
+            BitDepth::Five
+        } else {
+            BitDepth::Four
+        }
+    }
+
+    pub fn bits(self) -> u8 {
+        match self {
+            BitDepth::Four => 4,
+            BitDepth::Five => 5,
+        }
+    }
+
+    pub fn gray_levels(self) -> usize {
+        1 << self.bits()
+    }
+
+    /// Number of phase cells of a frame in the file.
+    fn cells_per_frame(self) -> usize {
+        self.gray_levels() * self.gray_levels() / 4
+    }
+
+    /// Index in [`PhaseMatrix::cells`] of the `n`th phase cell of a frame in
+    /// the file.
+    ///
+    /// A column of a 4-bit frame only fills the first half of a column of
+    /// the matrix.
+    fn cell_index(self, n: usize) -> usize {
+        let per_column = self.gray_levels() / 4;
+        (n / per_column) * CELLS_PER_COLUMN + n % per_column
+    }
+}
+
+/// The phases to apply in one frame for every pair of intensities.
+///
+/// Rows are the intensity a pixel goes to and columns the intensity it comes
+/// from, so the phase of a transition is at `(to, from)`. The .wbf file
+/// stores the 32 target intensities of one source intensity after the other,
+/// which is the `matrix[j][i]` of other decoders with `i` the source.
+/// [`PhaseMatrix::phase`] takes a [`Transition`] instead to avoid mixing up
+/// the two.
+///
+/// Phases are packed 4 to a byte in the same order as the phase cells of the
+/// .wbf file, so a frame takes 256 bytes instead of the 1 KiB a
+/// `[[Phase; 32]; 32]` needs. Frames of 4-bit files only use the first 16
+/// rows and columns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhaseMatrix {
+    cells: [u8; INTENSITY_VALUES * CELLS_PER_COLUMN],
+}
+
+impl PhaseMatrix {
+    /// A matrix filled with [`Phase::Noop`].
+    pub fn new() -> Self {
+        PhaseMatrix {
+            cells: [0; INTENSITY_VALUES * CELLS_PER_COLUMN],
+        }
+    }
+
+    fn position(row: usize, column: usize) -> (usize, u8) {
+        if row >= INTENSITY_VALUES || column >= INTENSITY_VALUES {
+            panic!("index out of bounds for PhaseMatrix: ({row}, {column})")
+        }
+
+        (column * CELLS_PER_COLUMN + row / 4, (row % 4) as u8)
+    }
+
+    /// The phase to apply in this frame for `transition`.
+    ///
+    /// # Panics
+    ///
+    /// If `transition` is out of bounds.
+    pub fn phase(&self, transition: Transition) -> Phase {
+        self[(transition.to as usize, transition.from as usize)]
+    }
+
+    /// The phase at the given position, or `None` if it is out of bounds.
+    pub fn get(&self, row: usize, column: usize) -> Option<Phase> {
+        if row >= INTENSITY_VALUES || column >= INTENSITY_VALUES {
+            return None;
+        }
+
+        Some(self[(row, column)])
+    }
+
+    pub fn set(&mut self, row: usize, column: usize, phase: Phase) {
+        let (cell, index) = Self::position(row, column);
+        let shift = 6 - (2 * index);
+
+        self.cells[cell] = (self.cells[cell] & !(0b11 << shift)) | ((phase as u8) << shift);
+    }
+}
+
+impl Default for PhaseMatrix {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Index<(usize, usize)> for PhaseMatrix {
+    type Output = Phase;
+
+    fn index(&self, (row, column): (usize, usize)) -> &Self::Output {
+        let (cell, index) = Self::position(row, column);
+        PhaseCell(self.cells[cell]).phase(index)
+    }
+}
+
+/// Upper bound on the number of frames of a waveform, far above what real
+/// waveforms use, so that a corrupted block can't make decoding run forever.
+const MAX_FRAMES: usize = 1 << 12;
+
+/// A run of identical phase cells, which can span several frames.
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    cell: PhaseCell,
+    remaining: usize,
+
+    /// Offset of the run in the file.
+    offset: usize,
+}
+
+/// Iterator over the frames of an encoded waveform block, decoding them one
+/// at a time without allocating.
+///
+/// It stops after the first error.
+#[derive(Debug, Clone)]
+pub struct Frames<'a> {
+    input: Cursor<'a>,
+    end: usize,
+    bit_depth: BitDepth,
+    repeat_mode: bool,
+    run: Option<Run>,
+    count: usize,
+    done: bool,
+}
+
+impl<'a> Frames<'a> {
+    /// Decode the block of `length` bytes at the offset of `input`.
+    fn new(input: Cursor<'a>, length: usize, bit_depth: BitDepth) -> Self {
+        Frames {
+            end: input.offset() + length,
+            input,
+            bit_depth,
+            repeat_mode: true,
+            run: None,
+            count: 0,
+            done: false,
+        }
+    }
+
+    fn frame(&mut self) -> Result<Option<PhaseMatrix>, Error> {
+        let mut matrix = PhaseMatrix::new();
+        let mut cell = 0;
+        let cells = self.bit_depth.cells_per_frame();
+
+        loop {
+            if let Some(run) = &mut self.run {
+                let count = run.remaining.min(cells - cell);
+                for n in cell..cell + count {
+                    matrix.cells[self.bit_depth.cell_index(n)] = run.cell.0;
+                }
+                cell += count;
+                run.remaining -= count;
+
+                let offset = run.offset;
+                if run.remaining == 0 {
+                    self.run = None;
+                }
+
+                if cell == cells {
+                    if self.count == MAX_FRAMES {
+                        return Err(Error::at(
+                            ErrorKind::OverlongRun,
+                            self.input.location_at(offset),
+                        ));
+                    }
+
+                    self.count += 1;
+                    return Ok(Some(matrix));
+                }
+            }
+
+            if self.input.offset() >= self.end {
+                break;
+            }
+
+            let offset = self.input.offset();
+            let phases = self.input.u8()?;
+
+            if phases == 0xFC {
+                self.repeat_mode = !self.repeat_mode;
+                continue;
+            }
+
+            if phases == 0xFF {
+                break;
+            }
+
+            let mut repeat = 1;
+
+            if self.repeat_mode {
+                if self.input.offset() == self.end {
+                    return Err(Error::at(
+                        ErrorKind::TruncatedBlock,
+                        self.input.location_at(offset),
+                    ));
+                }
+
+                repeat = self.input.u8()? as usize + 1;
+            }
+
+            let phase_cell = PhaseCell::new(phases)
+                .map_err(|kind| Error::at(kind, self.input.location_at(offset)))?;
+
+            self.run = Some(Run {
+                cell: phase_cell,
+                remaining: repeat,
+                offset,
+            });
+        }
+
+        if cell != 0 {
+            return Err(Error::at(ErrorKind::TruncatedBlock, self.input.location()));
+        }
+
+        Ok(None)
+    }
+}
+
+impl Iterator for Frames<'_> {
+    type Item = Result<PhaseMatrix, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let frame = self.frame().transpose();
+        self.done = !matches!(frame, Some(Ok(_)));
+        frame
+    }
+}
+
+fn effective_frame_rate(frame_rate: u8) -> u8 {
+    if frame_rate == 0 { 85 } else { frame_rate }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Check the CRC32 stored in the header against the contents of the file.
+    ///
+    /// Some waveform files are known to ship with a bogus CRC32, so this can
+    /// be turned off to load them anyway.
+    pub verify_crc32: bool,
+
+    /// Keep the file in memory and only decode a waveform the first time it
+    /// is looked up, instead of decoding all of them while parsing.
+    pub lazy: bool,
+
+    /// Decode the file with this bit depth instead of the one given by its
+    /// header, for files with a wrong `luts` field.
+    pub bit_depth: Option<BitDepth>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            verify_crc32: true,
+            lazy: false,
+            bit_depth: None,
+        }
+    }
+}
+
+/// Directory holding the waveform file on the reMarkable 2.
+#[cfg(feature = "std")]
+const WAVEFORM_DIRECTORY: &str = "/usr/share/remarkable";
+
+/// Find the waveform file of the panel: the first .wbf file by name in
+/// `/usr/share/remarkable`.
+#[cfg(feature = "std")]
+pub fn discover_path() -> Result<Option<PathBuf>, io::Error> {
+    let mut paths = vec![];
+
+    for entry in fs::read_dir(WAVEFORM_DIRECTORY)? {
+        let path = entry?.path();
+
+        if path.extension().is_some_and(|extension| extension == "wbf") && path.is_file() {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+    Ok(paths.into_iter().next())
+}
+
+#[test]
+fn parse_pointer_test() {
+    let mut input = Cursor::new(&[0x5, 0x5, 0x6, 0x10]);
+
+    let p = pointer(&mut input).unwrap();
+
+    assert_eq!(p, 0x060505);
+
+    let mut input = Cursor::new(&[0x5, 0x5, 0x6, 0x11]);
+
+    let error = input
+        .field(Field::Modes, |input| input.index(3, |input| pointer(input)))
+        .unwrap_err();
+
+    let location = error.location().unwrap();
+    assert_eq!(location.offset, 3);
+    assert_eq!(location.path.to_string(), "modes[3].checksum");
+}
+
+#[test]
+fn phase_matrix_test() {
+    assert_eq!(size_of::<PhaseMatrix>(), 256);
+
+    let mut matrix = PhaseMatrix::new();
+    matrix.set(5, 3, Phase::Black);
+    matrix.set(6, 3, Phase::White);
+    matrix.set(5, 3, Phase::White);
+
+    assert_eq!(matrix.get(5, 3), Some(Phase::White));
+    assert_eq!(matrix.get(5, 32), None);
+    assert_eq!(matrix[(6, 3)], Phase::White);
+    assert_eq!(matrix[(3, 5)], Phase::Noop);
+    assert_eq!(matrix.cells[3 * CELLS_PER_COLUMN + 1], 0b00101000);
+}
+
+#[test]
+fn header_checksum_test() {
+    let mut bytes = [0u8; HEADER_LENGTH];
+    bytes[4] = 0x30;
+    bytes[20] = 0x12;
+    bytes[31] = 0x42;
+    bytes[37] = 0x07;
+    bytes[47] = 0x07;
+
+    assert!(header(&mut Cursor::new(&bytes)).is_ok());
+
+    bytes[36] = 0x01;
+    let error = header(&mut Cursor::new(&bytes)).unwrap_err();
+    assert!(matches!(
+        error.kind(),
+        ErrorKind::InvalidChecksum {
+            expected: 0x07,
+            actual: 0x08
+        }
+    ));
+    assert_eq!(
+        error.to_string(),
+        "header.checksum2 at offset 0x2f: invalid checksum: expected 0x7, actual 0x8"
+    );
+}
+
+#[test]
+fn four_bit_frames_test() {
+    // 64 cells: the first phase of the first two columns is black.
+    let block = [0x40, 0, 0x00, 2, 0x10, 0, 0x00, 58, 0xFF];
+
+    let frames = Frames::new(Cursor::new(&block), block.len(), BitDepth::Four)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(frames.len(), 1);
+
+    let black = Transition::all()
+        .filter(|transition| frames[0].phase(*transition) == Phase::Black)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        black,
+        [Transition { from: 0, to: 0 }, Transition { from: 1, to: 1 }]
+    );
+
+    let error = Frames::new(Cursor::new(&block), block.len(), BitDepth::Five)
+        .next()
+        .unwrap()
+        .unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::TruncatedBlock));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn verify_crc32_test() {
+    let mut bytes = EXAMPLE_FILE.to_vec();
+
+    // Turn the first run of no-op cells of the INIT block into a run of
+    // black ones, which no additive checksum covers.
+    let cell = pointer_at(&bytes, waveform_pointer_offset(&bytes, 0, 0));
+    assert_eq!(bytes[cell], 0x00);
+    bytes[cell] = 0x55;
+
+    let error = Table::parse(&mut std::io::Cursor::new(&bytes)).unwrap_err();
+    let stored = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    assert!(matches!(
+        error.kind(),
+        ErrorKind::InvalidCrc32 { expected, actual } if *expected == stored && *actual != stored
+    ));
+    assert_eq!(
+        error.location().unwrap().path.to_string(),
+        "header.checksum"
+    );
+
+    let options = ParseOptions {
+        verify_crc32: false,
+        ..Default::default()
+    };
+    let parsed = Table::parse_with_options(&mut std::io::Cursor::new(&bytes), &options).unwrap();
+    let lookup = parsed.lookup(Mode::INIT, 20).unwrap();
+    assert_eq!(
+        lookup.waveform[0].phase(Transition { from: 0, to: 0 }),
+        Phase::Black
+    );
+}
+
+/// A file written by [`Table::write`] with the INIT, DU and GC16 modes and 3
+/// temperature ranges, the last one with equal bounds. INIT has a single
+/// frame that all the ranges share, while the other modes have more frames
+/// in warmer ranges.
+#[cfg(all(test, feature = "std"))]
+const EXAMPLE_FILE: &[u8] = include_bytes!("testdata/example.wbf");
+
+/// Read the pointer at an offset of a file.
+#[cfg(all(test, feature = "std"))]
+fn pointer_at(bytes: &[u8], offset: usize) -> usize {
+    u24_from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2]]) as usize
+}
+
+/// The offset of the pointer to the waveform of a mode and temperature range
+/// in a file.
+#[cfg(all(test, feature = "std"))]
+fn waveform_pointer_offset(bytes: &[u8], mode_index: usize, range_index: usize) -> usize {
+    let wmta = pointer_at(bytes, 32);
+    pointer_at(bytes, wmta + 4 * mode_index) + 4 * range_index
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn waveform_file_test() {
+    let table = Table::parse(&mut std::io::Cursor::new(EXAMPLE_FILE)).unwrap();
+
+    let file = WaveformFile::parse(EXAMPLE_FILE, &Default::default()).unwrap();
+    let lookup = file.lookup(Mode::GC16, 30, ClampingPolicy::Clamp).unwrap();
+    assert_eq!(
+        &lookup.frames.collect::<Result<Vec<_>, _>>().unwrap(),
+        table.waveform(2, 1).unwrap()
+    );
+}
+
+/// Update the file size, the header checksums and the CRC32 of a file after
+/// editing it.
+#[cfg(all(test, feature = "std"))]
+fn seal(bytes: &mut [u8]) {
+    let filesize = bytes.len() as u32;
+    bytes[4..8].copy_from_slice(&filesize.to_le_bytes());
+    bytes[31] = checksum::additive_checksum(bytes[CHECKSUM1_RANGE].iter());
+    bytes[47] = checksum::additive_checksum(bytes[CHECKSUM2_RANGE].iter());
+
+    let mut crc = checksum::Crc32::new();
+    crc.update(&bytes[4..]);
+    bytes[0..4].copy_from_slice(&crc.finish().to_le_bytes());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn mode_table_address_test() {
+    let table = Table::from_bytes(EXAMPLE_FILE).unwrap();
+    let mut bytes = EXAMPLE_FILE.to_vec();
+
+    // Move the mode table to the end of the file and clobber the old one, so
+    // that it is no longer next to the filename block.
+    let wmta = pointer_at(&bytes, 32);
+    let mode_table = bytes[wmta..wmta + 12].to_vec();
+    bytes[wmta..wmta + 12].fill(0);
+    let moved = bytes.len() as u32;
+    bytes.extend(mode_table);
+    bytes[32..35].copy_from_slice(&moved.to_le_bytes()[..3]);
+    seal(&mut bytes);
+
+    let parsed = Table::from_bytes(&bytes).unwrap();
+    assert_eq!(parsed.info().wmta, moved);
+    assert_eq!(parsed.info().filename, b"test.wbf");
+    for mode_index in 0..3 {
+        assert_eq!(
+            parsed.waveform(mode_index, 0).unwrap(),
+            table.waveform(mode_index, 0).unwrap()
+        );
+    }
+}
+
+#[test]
+fn mode_layout_test() {
+    use Mode::*;
+
+    let modes = |mode_version, count| Mode::layout(count, mode_version).collect::<Vec<_>>();
+
+    assert_eq!(modes(0x10, 4), [INIT, DU, GC16, GC4]);
+    assert_eq!(modes(0x13, 5), [INIT, DU, GC16, GC4, A2]);
+    assert_eq!(modes(0x18, 7), [INIT, DU, GC16, GL16, GLR16, GLD16, A2]);
+    assert_eq!(
+        modes(0x19, 9),
+        [INIT, DU, GC16, GL16, GLR16, GLD16, A2, DU4, Other(8)]
+    );
+    assert_eq!(modes(0x42, 3), [INIT, DU, GC16]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn mode_not_found_test() {
+    let table = |adhesive_run| {
+        let info = WaveformInfo {
+            adhesive_run,
+            ..WaveformInfo::example()
+        };
+        Table::new(info, vec![0, 50], vec![vec![vec![PhaseMatrix::new()]]; 4])
+    };
+
+    let legacy = table(0x10);
+    assert!(legacy.lookup(Mode::GC4, 20).is_ok());
+    let error = legacy.lookup(Mode::GL16, 20).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::ModeNotFound(Mode::GL16)));
+
+    // Unknown versions fall back to the reMarkable 2's layout.
+    let unknown = table(0x42);
+    assert!(unknown.lookup(Mode::GL16, 20).is_ok());
+    let error = unknown.lookup(Mode::A2, 20).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::ModeNotFound(Mode::A2)));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn size_mismatch_test() {
+    let mut bytes = EXAMPLE_FILE.to_vec();
+    bytes.push(0);
+
+    let error = Table::from_bytes(&bytes).unwrap_err();
+    assert!(matches!(
+        error.kind(),
+        ErrorKind::SizeMismatch { expected, actual }
+            if *expected as usize == bytes.len() - 1 && *actual as usize == bytes.len()
+    ));
+    assert_eq!(
+        error.location().unwrap().path.to_string(),
+        "header.filesize"
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn dangling_pointer_test() {
+    let mut bytes = EXAMPLE_FILE.to_vec();
+
+    let pointer = bytes.len() as u32 + 0x100;
+    let temps = waveform_pointer_offset(&bytes, 0, 0);
+    bytes[temps..temps + 3].copy_from_slice(&pointer.to_le_bytes()[..3]);
+    bytes[temps + 3] = checksum::additive_checksum(bytes[temps..temps + 3].iter());
+    seal(&mut bytes);
+
+    let error = Table::from_bytes(&bytes).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::DanglingPointer(p) if *p == pointer));
+    let location = error.location().unwrap();
+    assert_eq!(location.path.to_string(), "modes[0].temps[0].pointer");
+    assert_eq!(location.offset, temps);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn overlong_run_test() {
+    let mut bytes = EXAMPLE_FILE.to_vec();
+
+    // Replace the last block, which belongs to GC16 in the last temperature
+    // range, with one frame more than decoding accepts, each run filling a
+    // whole frame.
+    let block = pointer_at(&bytes, waveform_pointer_offset(&bytes, 2, 2));
+    bytes.truncate(block);
+    for _ in 0..=MAX_FRAMES {
+        bytes.extend([0x00, 0xFF]);
+    }
+    bytes.push(0xFF);
+    seal(&mut bytes);
+
+    // Lazy tables only find out on lookup.
+    let table = Table::from_bytes(&bytes).unwrap();
+    let error = table.waveform(2, 2).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::OverlongRun));
+    let location = error.location().unwrap();
+    assert_eq!(location.path.to_string(), "modes[2].temps[2].waveform");
+    assert_eq!(location.offset, block + 2 * MAX_FRAMES);
+
+    let error = Table::parse(&mut std::io::Cursor::new(&bytes)).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::OverlongRun));
+}
@@ -0,0 +1,267 @@
+use std::io::{self, Write};
+
+use crate::rm2::checksum;
+
+use super::{BitDepth, Phase, PhaseMatrix, Transition};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// Largest block of a stored (uncompressed) deflate stream.
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+/// Largest prime below 2^16, which the Adler-32 sums are taken modulo.
+const ADLER32_MODULUS: u32 = 65521;
+
+fn shade(phase: Phase) -> u8 {
+    match phase {
+        Phase::Noop => Image::NOOP,
+        Phase::Black => Image::BLACK,
+        Phase::White => Image::WHITE,
+    }
+}
+
+/// An 8-bit grayscale image of phase matrices.
+///
+/// Frames are drawn with the intensity a pixel comes from on the x axis and
+/// the one it goes to on the y axis, so a transition is drawn at
+/// `(from, to)` and the diagonal holds the transitions that keep a pixel at
+/// the same intensity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Shade of the pixels of transitions driven to black in a frame.
+    pub const BLACK: u8 = 0x00;
+
+    /// Shade of the pixels of transitions driven to white in a frame.
+    pub const WHITE: u8 = 0xFF;
+
+    /// Shade of the pixels of transitions left alone in a frame.
+    pub const NOOP: u8 = 0x80;
+
+    /// Shade of the lines separating the frames of a sheet, darker than
+    /// [`Image::NOOP`] so that they can't be mistaken for it.
+    pub const GRID: u8 = 0x40;
+
+    fn new(width: usize, height: usize, shade: u8) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![shade; width * height],
+        }
+    }
+
+    /// One frame of a waveform, each transition taking `scale` by `scale`
+    /// pixels.
+    ///
+    /// # Panics
+    ///
+    /// If `scale` is 0.
+    pub fn frame(matrix: &PhaseMatrix, bit_depth: BitDepth, scale: usize) -> Self {
+        assert!(scale > 0, "scale must be at least 1");
+
+        let size = bit_depth.gray_levels() * scale;
+        let mut image = Image::new(size, size, Image::NOOP);
+        image.draw(matrix, bit_depth, scale, 0, 0);
+        image
+    }
+
+    /// Every frame of a waveform side by side, `columns` frames per row, in
+    /// a grid of 1 pixel wide lines.
+    ///
+    /// # Panics
+    ///
+    /// If `scale` or `columns` is 0.
+    pub fn sheet(
+        frames: &[PhaseMatrix],
+        bit_depth: BitDepth,
+        scale: usize,
+        columns: usize,
+    ) -> Self {
+        assert!(scale > 0, "scale must be at least 1");
+        assert!(columns > 0, "a sheet needs at least 1 column");
+
+        let tile = bit_depth.gray_levels() * scale;
+        let columns = columns.min(frames.len()).max(1);
+        let rows = frames.len().div_ceil(columns);
+
+        let mut image = Image::new(columns * (tile + 1) + 1, rows * (tile + 1) + 1, Image::GRID);
+
+        for (i, matrix) in frames.iter().enumerate() {
+            let x = (i % columns) * (tile + 1) + 1;
+            let y = (i / columns) * (tile + 1) + 1;
+            image.draw(matrix, bit_depth, scale, x, y);
+        }
+
+        image
+    }
+
+    fn draw(
+        &mut self,
+        matrix: &PhaseMatrix,
+        bit_depth: BitDepth,
+        scale: usize,
+        x: usize,
+        y: usize,
+    ) {
+        let levels = bit_depth.gray_levels() as u8;
+
+        for to in 0..levels {
+            for from in 0..levels {
+                let shade = shade(matrix.phase(Transition { from, to }));
+
+                for row in 0..scale {
+                    let start =
+                        (y + to as usize * scale + row) * self.width + x + from as usize * scale;
+                    self.pixels[start..start + scale].fill(shade);
+                }
+            }
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The shades of the pixels, row by row.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Write the image as a binary PGM (`P5`) file.
+    pub fn write_pgm<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        write!(output, "P5\n{} {}\n255\n", self.width, self.height)?;
+        output.write_all(&self.pixels)
+    }
+
+    /// Write the image as a PNG file.
+    ///
+    /// The image data is stored without compression, which keeps the
+    /// encoder small at the cost of larger files.
+    pub fn write_png<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "image too large for PNG");
+        let width = u32::try_from(self.width).map_err(|_| too_large())?;
+        let height = u32::try_from(self.height).map_err(|_| too_large())?;
+
+        let mut header = vec![];
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        // 8 bits per pixel, grayscale, deflate, no filtering, no interlacing.
+        header.extend_from_slice(&[8, 0, 0, 0, 0]);
+
+        // Each row starts with the type of filter applied to it, none here.
+        let mut scanlines = Vec::with_capacity((self.width + 1) * self.height);
+        for row in self.pixels.chunks(self.width.max(1)) {
+            scanlines.push(0);
+            scanlines.extend_from_slice(row);
+        }
+
+        output.write_all(&PNG_SIGNATURE)?;
+        png_chunk(b"IHDR", &header, output)?;
+        png_chunk(b"IDAT", &zlib_stored(&scanlines), output)?;
+        png_chunk(b"IEND", &[], output)
+    }
+}
+
+fn png_chunk<W: Write>(kind: &[u8; 4], data: &[u8], output: &mut W) -> io::Result<()> {
+    let length = u32::try_from(data.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "PNG chunk too large"))?;
+
+    let mut crc = checksum::Crc32::new();
+    crc.update(kind);
+    crc.update(data);
+
+    output.write_all(&length.to_be_bytes())?;
+    output.write_all(kind)?;
+    output.write_all(data)?;
+    output.write_all(&crc.finish().to_be_bytes())
+}
+
+/// A zlib stream holding `data` in stored deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // Deflate with a 32 KiB window and no preset dictionary, which makes the
+    // two header bytes a multiple of 31.
+    let mut output = vec![0x78, 0x01];
+
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        output.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let length = block.len() as u16;
+
+        output.push(last as u8);
+        output.extend_from_slice(&length.to_le_bytes());
+        output.extend_from_slice(&(!length).to_le_bytes());
+        output.extend_from_slice(block);
+    }
+
+    output.extend_from_slice(&adler32(data).to_be_bytes());
+
+    output
+}
+
+/// The Adler-32 checksum of `bytes`, which ends zlib streams.
+fn adler32(bytes: &[u8]) -> u32 {
+    let (a, b) = bytes.iter().fold((1, 0), |(a, b), byte| {
+        let a = (a + *byte as u32) % ADLER32_MODULUS;
+        (a, (b + a) % ADLER32_MODULUS)
+    });
+
+    (b << 16) | a
+}
+
+#[test]
+fn render_test() {
+    let mut matrix = PhaseMatrix::new();
+    matrix.set(1, 0, Phase::Black);
+    matrix.set(0, 1, Phase::White);
+
+    let frame = Image::frame(&matrix, BitDepth::Four, 2);
+    assert_eq!((frame.width(), frame.height()), (32, 32));
+    // From 0 to 1 is on the second row of transitions.
+    assert_eq!(
+        frame.pixels()[2 * 32..2 * 32 + 3],
+        [Image::BLACK, Image::BLACK, Image::NOOP]
+    );
+    assert_eq!(frame.pixels()[2..4], [Image::WHITE, Image::WHITE]);
+
+    let sheet = Image::sheet(
+        &[matrix.clone(), matrix.clone(), matrix],
+        BitDepth::Five,
+        1,
+        2,
+    );
+    assert_eq!((sheet.width(), sheet.height()), (67, 67));
+    assert_eq!(sheet.pixels()[0], Image::GRID);
+    assert_eq!(sheet.pixels()[67 + 1], Image::NOOP);
+    assert_eq!(sheet.pixels()[2 * 67 + 34], Image::BLACK);
+    assert_eq!(
+        sheet.pixels()[34 * 67 + 34..34 * 67 + 67],
+        [Image::GRID; 33]
+    );
+
+    let mut pgm = vec![];
+    frame.write_pgm(&mut pgm).unwrap();
+    assert!(pgm.starts_with(b"P5\n32 32\n255\n"));
+    assert_eq!(pgm.len(), 13 + 32 * 32);
+
+    let mut png = vec![];
+    sheet.write_png(&mut png).unwrap();
+    assert!(png.starts_with(&PNG_SIGNATURE));
+    assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]));
+    // Signature, 3 chunks, zlib header, 1 stored block and Adler-32.
+    assert_eq!(png.len(), 8 + 25 + 12 + 2 + 5 + 68 * 67 + 4 + 12);
+
+    assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+}
@@ -0,0 +1,463 @@
+use alloc::{borrow::Cow, collections::BTreeMap, format, vec, vec::Vec};
+#[cfg(not(feature = "std"))]
+use core::cell::OnceCell as OnceLock;
+#[cfg(feature = "std")]
+use std::{
+    io::{Read, Seek, SeekFrom},
+    sync::OnceLock,
+};
+
+use super::{
+    BitDepth, ClampingPolicy, Error, ErrorKind, Frames, Mode, ParseOptions, PhaseMatrix,
+    TemperatureMatch, TemperatureRange, Transition, TransitionPhases, WaveformFile, WaveformInfo,
+    effective_frame_rate, find_waveform, in_waveform, temperature,
+};
+use crate::byte_reader::Cursor;
+
+pub type Waveform = Vec<PhaseMatrix>;
+
+/// Locate the encoded waveform blocks, assuming that each one ends where the
+/// next one starts.
+///
+/// Modes and temperature ranges often point to the same block, so each block
+/// is only listed once, and the returned indices map each mode and
+/// temperature range to its block.
+fn locate_blocks(pointers: Vec<Vec<u32>>, filesize: u32) -> (Vec<Block>, Vec<Vec<usize>>) {
+    let mut unique: Vec<u32> = pointers.iter().flatten().copied().collect();
+    unique.sort();
+    unique.dedup();
+
+    // The first mode and temperature range using each block, to tell where
+    // decoding errors come from.
+    let mut first_use = BTreeMap::new();
+    for (mode, pointers) in pointers.iter().enumerate() {
+        for (range, pointer) in pointers.iter().enumerate() {
+            first_use.entry(*pointer).or_insert((mode, range));
+        }
+    }
+
+    let blocks = unique
+        .iter()
+        .chain([filesize].iter())
+        .collect::<Vec<_>>()
+        .windows(2)
+        .map(|ps| Block {
+            pointer: *ps[0],
+            length: ps[1] - ps[0],
+            first_use: first_use[ps[0]],
+            waveform: OnceLock::new(),
+        })
+        .collect();
+
+    let indices: BTreeMap<u32, usize> = unique.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+
+    let block_indices = pointers
+        .into_iter()
+        .map(|pointers| pointers.iter().map(|pointer| indices[pointer]).collect())
+        .collect();
+
+    (blocks, block_indices)
+}
+
+/// A waveform block of the file, decoded on first use.
+#[derive(Debug)]
+pub(super) struct Block {
+    pointer: u32,
+    length: u32,
+
+    /// Indices of the first mode and temperature range using the block.
+    first_use: (usize, usize),
+
+    waveform: OnceLock<Waveform>,
+}
+
+impl Block {
+    fn decoded(waveform: Waveform) -> Self {
+        Block {
+            pointer: 0,
+            length: 0,
+            first_use: (0, 0),
+            waveform: OnceLock::from(waveform),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Table<'a> {
+    pub frame_rate: u8,
+    pub(super) info: WaveformInfo,
+    pub(super) bit_depth: BitDepth,
+    modes: Vec<Mode>,
+    pub(super) temperatures: Vec<u8>,
+    pub(super) blocks: Vec<Block>,
+
+    /// Index in `blocks` of the waveform for each mode and temperature range.
+    pub(super) block_indices: Vec<Vec<usize>>,
+
+    /// Contents of the file. It is dropped once parsing has decoded every
+    /// block, and kept for the lifetime of the table with
+    /// [`ParseOptions::lazy`], even after every block has been looked up.
+    source: Cow<'a, [u8]>,
+
+    clamping: ClampingPolicy,
+}
+
+/// The waveform found for a mode and temperature by [`Table::lookup`].
+#[derive(Debug, Clone, Copy)]
+pub struct Lookup<'a> {
+    pub waveform: &'a Waveform,
+
+    /// The temperature range the waveform is meant for.
+    pub range: TemperatureRange,
+
+    /// Whether the temperature was inside of `range` or had to be clamped.
+    pub temperature: TemperatureMatch,
+
+    pub bit_depth: BitDepth,
+}
+
+impl<'a> Lookup<'a> {
+    /// Whether both lookups point to the same waveform block of the file.
+    pub fn same_waveform(&self, other: &Lookup<'_>) -> bool {
+        core::ptr::eq(self.waveform, other.waveform)
+    }
+
+    /// Number of frames of the waveform, which is the same for every
+    /// transition.
+    pub fn frame_count(&self) -> usize {
+        self.waveform.len()
+    }
+
+    /// The phases to apply, frame by frame, for a pixel going through
+    /// `transition`.
+    ///
+    /// # Panics
+    ///
+    /// If `transition` is out of bounds for the bit depth of the waveform.
+    pub fn transition(&self, transition: Transition) -> TransitionPhases<'a> {
+        let levels = self.bit_depth.gray_levels();
+        assert!(
+            (transition.from as usize) < levels && (transition.to as usize) < levels,
+            "transition out of bounds for {levels} gray levels: {transition:?}"
+        );
+
+        TransitionPhases::new(self.waveform, transition)
+    }
+}
+
+impl Table<'static> {
+    /// Build a table from its parts.
+    ///
+    /// `temperatures` holds the boundaries of the temperature ranges, and
+    /// `waveforms` holds one waveform per temperature range for each mode.
+    ///
+    /// Identical waveforms are only stored once.
+    pub fn new(info: WaveformInfo, temperatures: Vec<u8>, waveforms: Vec<Vec<Waveform>>) -> Self {
+        let mut blocks: Vec<Block> = vec![];
+
+        let block_indices = waveforms
+            .into_iter()
+            .map(|waveforms| {
+                waveforms
+                    .into_iter()
+                    .map(|waveform| {
+                        let existing = blocks
+                            .iter()
+                            .position(|block| block.waveform.get() == Some(&waveform));
+
+                        existing.unwrap_or_else(|| {
+                            blocks.push(Block::decoded(waveform));
+                            blocks.len() - 1
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        Table {
+            frame_rate: effective_frame_rate(info.frame_rate),
+            bit_depth: BitDepth::from_luts(info.luts),
+            modes: Mode::layout(block_indices.len(), info.adhesive_run).collect(),
+            info,
+            temperatures,
+            blocks,
+            block_indices,
+            source: Cow::Borrowed(&[]),
+            clamping: ClampingPolicy::default(),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn parse<R: Read + Seek>(input: &mut R) -> Result<Self, Error> {
+        Self::parse_with_options(input, &ParseOptions::default())
+    }
+
+    #[cfg(feature = "std")]
+    pub fn parse_with_options<R: Read + Seek>(
+        input: &mut R,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
+        let mut source = vec![];
+        input.seek(SeekFrom::Start(0))?;
+        input.read_to_end(&mut source)?;
+
+        Table::from_source(Cow::Owned(source), options)
+    }
+}
+
+impl<'a> Table<'a> {
+    /// Parse a table from a .wbf file in memory without copying it.
+    ///
+    /// Waveforms are decoded from `bytes` the first time they are looked up,
+    /// like with [`ParseOptions::lazy`].
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, Error> {
+        let options = ParseOptions {
+            lazy: true,
+            ..Default::default()
+        };
+
+        Self::from_bytes_with_options(bytes, &options)
+    }
+
+    pub fn from_bytes_with_options(bytes: &'a [u8], options: &ParseOptions) -> Result<Self, Error> {
+        Self::from_source(Cow::Borrowed(bytes), options)
+    }
+
+    fn from_source(source: Cow<'a, [u8]>, options: &ParseOptions) -> Result<Self, Error> {
+        let file = WaveformFile::parse(&source, options)?;
+
+        let pointers = (0..file.modes().len())
+            .map(|mode_index| {
+                (0..file.temperature_ranges().len())
+                    .map(|range_index| file.pointer(mode_index, range_index))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (blocks, block_indices) = locate_blocks(pointers, file.header.filesize);
+
+        let info = WaveformInfo::new(
+            &file.header,
+            file.filename.to_vec(),
+            file.extra_info.map(<[u8]>::to_vec),
+        );
+        let temperatures = file.temperatures.to_vec();
+        let modes = file.modes().collect();
+
+        let mut table = Table {
+            frame_rate: file.frame_rate(),
+            bit_depth: file.bit_depth(),
+            info,
+            modes,
+            temperatures,
+            blocks,
+            block_indices,
+            source,
+            clamping: ClampingPolicy::default(),
+        };
+
+        if !options.lazy {
+            table.decode_all()?;
+            table.source = Cow::Borrowed(&[]);
+        }
+
+        Ok(table)
+    }
+
+    /// The waveform for the mode and temperature range at the given indices
+    /// of [`Table::modes`] and [`Table::temperature_ranges`], decoding it if
+    /// this is the first time it is used.
+    ///
+    /// Unlike [`Table::lookup`], it doesn't go through temperature matching,
+    /// so every range can be reached, even one whose bounds are equal.
+    pub fn waveform(&self, mode_index: usize, range_index: usize) -> Result<&Waveform, Error> {
+        let index = self
+            .block_indices
+            .get(mode_index)
+            .and_then(|indices| indices.get(range_index))
+            .ok_or_else(|| {
+                ErrorKind::InvalidLayout(format!(
+                    "no waveform for mode {mode_index} and temperature range {range_index}"
+                ))
+            })?;
+
+        self.block(*index)
+    }
+
+    pub(super) fn block(&self, index: usize) -> Result<&Waveform, Error> {
+        let block = &self.blocks[index];
+
+        if let Some(waveform) = block.waveform.get() {
+            return Ok(waveform);
+        }
+
+        let mut input = Cursor::new(&self.source);
+        input.seek(block.pointer as usize);
+
+        let (mode_index, range_index) = block.first_use;
+        let waveform = in_waveform(&mut input, mode_index, range_index, |input| {
+            Frames::new(input.clone(), block.length as usize, self.bit_depth).collect()
+        })?;
+
+        Ok(block.waveform.get_or_init(|| waveform))
+    }
+
+    fn decode_all(&self) -> Result<(), Error> {
+        for i in 0..self.blocks.len() {
+            self.block(i)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn bit_depth(&self) -> BitDepth {
+        self.bit_depth
+    }
+
+    /// Number of intensities the waveforms of the table go between.
+    pub fn gray_levels(&self) -> usize {
+        self.bit_depth.gray_levels()
+    }
+
+    /// Metadata about the waveform file the table was parsed from.
+    pub fn info(&self) -> &WaveformInfo {
+        &self.info
+    }
+
+    /// Memory used by the decoded waveforms and by the file contents kept
+    /// for decoding them, in bytes. The file contents of a lazy table count
+    /// even once every waveform is decoded.
+    pub fn memory_usage(&self) -> usize {
+        let waveforms: usize = self
+            .blocks
+            .iter()
+            .filter_map(|block| block.waveform.get())
+            .map(|waveform| size_of::<Waveform>() + waveform.capacity() * size_of::<PhaseMatrix>())
+            .sum();
+
+        let source = match &self.source {
+            Cow::Borrowed(_) => 0,
+            Cow::Owned(source) => source.capacity(),
+        };
+
+        waveforms + source
+    }
+
+    /// The modes contained in the table, named after the layout of its mode
+    /// version. Modes the layout has no name for are [`Mode::Other`].
+    pub fn modes(&self) -> &[Mode] {
+        &self.modes
+    }
+
+    /// The temperature ranges covered by the table, from coldest to warmest.
+    pub fn temperature_ranges(&self) -> impl ExactSizeIterator<Item = TemperatureRange> + '_ {
+        temperature::ranges(&self.temperatures)
+    }
+
+    /// Set what [`Table::lookup`] does with temperatures outside of the
+    /// ranges of the table. Defaults to [`ClampingPolicy::Clamp`].
+    pub fn set_clamping_policy(&mut self, policy: ClampingPolicy) {
+        self.clamping = policy;
+    }
+
+    /// Find the waveform for a mode at the given temperature.
+    ///
+    /// With [`ParseOptions::lazy`], the waveform is decoded the first time
+    /// it is looked up, which can fail if its block is corrupted.
+    pub fn lookup(&self, mode: Mode, temperature: u8) -> Result<Lookup<'_>, Error> {
+        let (mode_index, range_index, range, matched) = find_waveform(
+            self.modes.iter().copied(),
+            self.temperature_ranges(),
+            mode,
+            temperature,
+            self.clamping,
+        )?;
+
+        Ok(Lookup {
+            waveform: self.waveform(mode_index, range_index)?,
+            range,
+            temperature: matched,
+            bit_depth: self.bit_depth,
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn table_test() {
+    use super::EXAMPLE_FILE;
+
+    let table = Table::parse(&mut std::io::Cursor::new(EXAMPLE_FILE)).unwrap();
+
+    let options = ParseOptions {
+        lazy: true,
+        ..Default::default()
+    };
+    let lazy =
+        Table::parse_with_options(&mut std::io::Cursor::new(EXAMPLE_FILE), &options).unwrap();
+    let memory_usage = lazy.memory_usage();
+    assert_eq!(
+        lazy.lookup(Mode::GC16, 30).unwrap().waveform,
+        table.waveform(2, 1).unwrap()
+    );
+    assert!(lazy.memory_usage() > memory_usage);
+
+    // Looking it up again reuses the decoded waveform.
+    let memory_usage = lazy.memory_usage();
+    lazy.lookup(Mode::GC16, 30).unwrap();
+    assert_eq!(lazy.memory_usage(), memory_usage);
+
+    let borrowed = Table::from_bytes(EXAMPLE_FILE).unwrap();
+    assert_eq!(borrowed.memory_usage(), 0);
+    assert_eq!(
+        borrowed.lookup(Mode::DU, 0).unwrap().waveform,
+        table.waveform(1, 0).unwrap()
+    );
+
+    // INIT points to the same block for every temperature range.
+    for parsed in [&table, &lazy, &borrowed] {
+        assert_eq!(parsed.blocks.len(), 7);
+
+        let init = |temperature| parsed.lookup(Mode::INIT, temperature).unwrap();
+        let du = |temperature| parsed.lookup(Mode::DU, temperature).unwrap();
+        assert!(init(0).same_waveform(&init(30)));
+        assert!(!du(0).same_waveform(&du(30)));
+    }
+
+    // A 4-bit file whose header claims 5 bits.
+    let info = WaveformInfo {
+        luts: 0,
+        ..table.info.clone()
+    };
+    let waveforms = (0..3)
+        .map(|i| {
+            (0..3)
+                .map(|j| table.waveform(i, j).unwrap().clone())
+                .collect()
+        })
+        .collect();
+    let table = Table::new(info, table.temperatures.clone(), waveforms);
+    assert_eq!(table.bit_depth(), BitDepth::Four);
+
+    let mut bytes = vec![];
+    table.write(&mut bytes).unwrap();
+    bytes[36] = 0x04;
+    super::seal(&mut bytes);
+
+    let options = ParseOptions {
+        bit_depth: Some(BitDepth::Four),
+        ..Default::default()
+    };
+    let parsed = Table::from_bytes_with_options(&bytes, &options).unwrap();
+
+    let mut rewritten = vec![];
+    parsed.write(&mut rewritten).unwrap();
+    assert_eq!(rewritten[36], 0x00);
+
+    let reparsed = Table::from_bytes(&rewritten).unwrap();
+    assert_eq!(reparsed.bit_depth(), BitDepth::Four);
+    assert_eq!(
+        reparsed.waveform(2, 1).unwrap(),
+        parsed.waveform(2, 1).unwrap()
+    );
+}
@@ -0,0 +1,100 @@
+/// A range of temperatures in Celsius covered by one waveform, from `min`
+/// inclusive to `max` exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemperatureRange {
+    pub min: u8,
+    pub max: u8,
+}
+
+impl TemperatureRange {
+    pub fn contains(&self, temperature: u8) -> bool {
+        self.min <= temperature && temperature < self.max
+    }
+}
+
+/// How a temperature was matched to one of the ranges of a table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemperatureMatch {
+    /// The temperature is inside the range.
+    InRange,
+
+    /// The temperature is colder than the first range, so the first range
+    /// was used.
+    ClampedLow,
+
+    /// The temperature is warmer than the last range, so the last range was
+    /// used.
+    ClampedHigh,
+}
+
+/// What to do when looking up a temperature outside of the ranges of a table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClampingPolicy {
+    /// Use the closest range.
+    #[default]
+    Clamp,
+
+    /// Return [`super::ErrorKind::TemperatureOutOfRange`].
+    Reject,
+}
+
+/// The ranges between consecutive temperature boundaries.
+pub(super) fn ranges(boundaries: &[u8]) -> impl ExactSizeIterator<Item = TemperatureRange> + '_ {
+    boundaries.windows(2).map(|bounds| TemperatureRange {
+        min: bounds[0],
+        max: bounds[1],
+    })
+}
+
+/// Find the range containing `temperature`, or the closest one if
+/// `temperature` is outside of all ranges.
+pub(super) fn find_range<I>(
+    ranges: I,
+    temperature: u8,
+) -> Option<(usize, TemperatureRange, TemperatureMatch)>
+where
+    I: ExactSizeIterator<Item = TemperatureRange>,
+{
+    let last = ranges.len().checked_sub(1)?;
+
+    for (i, range) in ranges.enumerate() {
+        if range.contains(temperature) {
+            return Some((i, range, TemperatureMatch::InRange));
+        }
+
+        if i == 0 && temperature < range.min {
+            return Some((i, range, TemperatureMatch::ClampedLow));
+        }
+
+        if i == last {
+            return Some((i, range, TemperatureMatch::ClampedHigh));
+        }
+    }
+
+    None
+}
+
+#[test]
+fn find_range_test() {
+    let range = |min, max| TemperatureRange { min, max };
+    let ranges = |boundaries| ranges(boundaries);
+    let boundaries = [0, 10, 20, 30];
+
+    assert_eq!(
+        find_range(ranges(&boundaries), 0),
+        Some((0, range(0, 10), TemperatureMatch::InRange))
+    );
+    assert_eq!(
+        find_range(ranges(&boundaries), 25),
+        Some((2, range(20, 30), TemperatureMatch::InRange))
+    );
+    assert_eq!(
+        find_range(ranges(&boundaries), 30),
+        Some((2, range(20, 30), TemperatureMatch::ClampedHigh))
+    );
+    assert_eq!(
+        find_range(ranges(&[5, 10]), 2),
+        Some((0, range(5, 10), TemperatureMatch::ClampedLow))
+    );
+    assert_eq!(find_range(ranges(&[5]), 2), None);
+}
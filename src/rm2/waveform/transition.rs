@@ -0,0 +1,119 @@
+use core::{iter::FusedIterator, slice};
+
+use super::{INTENSITY_VALUES, Phase, PhaseMatrix};
+
+/// A pixel going from one intensity to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Transition {
+    pub from: u8,
+    pub to: u8,
+}
+
+impl Transition {
+    /// The transition between two intensities, or `None` if one of them is
+    /// out of bounds.
+    pub fn new(from: u8, to: u8) -> Option<Self> {
+        let valid = |intensity| (intensity as usize) < INTENSITY_VALUES;
+        (valid(from) && valid(to)).then_some(Transition { from, to })
+    }
+
+    /// All transitions, from every intensity to every intensity.
+    pub fn all() -> impl Iterator<Item = Transition> {
+        (0..INTENSITY_VALUES as u8)
+            .flat_map(|from| (0..INTENSITY_VALUES as u8).map(move |to| Transition { from, to }))
+    }
+}
+
+/// The phases of one transition, frame by frame.
+#[derive(Debug, Clone)]
+pub struct TransitionPhases<'a> {
+    frames: slice::Iter<'a, PhaseMatrix>,
+    transition: Transition,
+}
+
+impl<'a> TransitionPhases<'a> {
+    /// # Panics
+    ///
+    /// If `transition` is out of bounds.
+    pub fn new(frames: &'a [PhaseMatrix], transition: Transition) -> Self {
+        assert!(
+            Transition::new(transition.from, transition.to).is_some(),
+            "transition out of bounds: {transition:?}"
+        );
+
+        TransitionPhases {
+            frames: frames.iter(),
+            transition,
+        }
+    }
+
+    /// Whether every remaining phase is [`Phase::Noop`], so the pixel does
+    /// not change.
+    pub fn is_noop(&self) -> bool {
+        self.clone().all(|phase| phase == Phase::Noop)
+    }
+
+    /// Number of remaining frames up to the last one that isn't a
+    /// [`Phase::Noop`], the frames after it leaving the pixel untouched.
+    pub fn active_len(&self) -> usize {
+        self.clone()
+            .rposition(|phase| phase != Phase::Noop)
+            .map_or(0, |last| last + 1)
+    }
+}
+
+impl Iterator for TransitionPhases<'_> {
+    type Item = Phase;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.frames.next().map(|frame| frame.phase(self.transition))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.frames.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for TransitionPhases<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.frames
+            .next_back()
+            .map(|frame| frame.phase(self.transition))
+    }
+}
+
+impl ExactSizeIterator for TransitionPhases<'_> {}
+
+impl FusedIterator for TransitionPhases<'_> {}
+
+#[test]
+fn transition_phases_test() {
+    let mut frames = [PhaseMatrix::new(), PhaseMatrix::new(), PhaseMatrix::new()];
+    frames[0].set(3, 1, Phase::Black);
+    frames[2].set(3, 1, Phase::White);
+
+    let transition = Transition::new(1, 3).unwrap();
+    let phases = TransitionPhases::new(&frames, transition);
+
+    assert_eq!(phases.len(), 3);
+    assert_eq!(phases.active_len(), 3);
+    assert!(!phases.is_noop());
+    assert_eq!(
+        phases.collect::<Vec<_>>(),
+        [Phase::Black, Phase::Noop, Phase::White]
+    );
+
+    let reversed = Transition { from: 3, to: 1 };
+    assert!(TransitionPhases::new(&frames, reversed).is_noop());
+    assert_eq!(TransitionPhases::new(&frames, reversed).active_len(), 0);
+    assert_eq!(
+        TransitionPhases::new(&frames[..2], transition).active_len(),
+        1
+    );
+
+    assert_eq!(Transition::new(1, 32), None);
+    assert_eq!(
+        Transition::all().count(),
+        INTENSITY_VALUES * INTENSITY_VALUES
+    );
+}
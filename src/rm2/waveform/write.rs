@@ -0,0 +1,311 @@
+use std::io::Write;
+
+use crate::rm2::checksum;
+
+use super::{
+    BitDepth, CHECKSUM1_RANGE, CHECKSUM2_RANGE, Error, ErrorKind, HEADER_LENGTH, Header, Table,
+    Waveform,
+};
+
+const POINTER_LENGTH: u32 = 4;
+
+/// Toggles between run-length encoded and literal phase cells.
+const REPEAT_TOGGLE: u8 = 0xFC;
+
+/// Marks the end of a waveform block.
+const END_OF_BLOCK: u8 = 0xFF;
+
+/// Longest run that can be stored in a single repeat count.
+const MAX_RUN: usize = 256;
+
+fn header_bytes(header: &Header) -> [u8; HEADER_LENGTH] {
+    let mut bytes = [0; HEADER_LENGTH];
+
+    bytes[0..4].copy_from_slice(&header.checksum.to_le_bytes());
+    bytes[4..8].copy_from_slice(&header.filesize.to_le_bytes());
+    bytes[8..12].copy_from_slice(&header.serial.to_le_bytes());
+    bytes[12] = header.run_type;
+    bytes[13] = header.fpl_platform;
+    bytes[14..16].copy_from_slice(&header.fpl_lot.to_le_bytes());
+    bytes[16] = header.adhesive_run;
+    bytes[17] = header.waveform_version;
+    bytes[18] = header.waveform_subversion;
+    bytes[19] = header.waveform_type;
+    bytes[20] = header.fpl_size;
+    bytes[21] = header.mfg_code;
+    bytes[22] = header.waveform_revision;
+    bytes[23] = header.old_frame_rate;
+    bytes[24] = header.frame_rate;
+    bytes[25] = header.vcom_offset;
+    bytes[28..31].copy_from_slice(&header.extra_info_addr.to_le_bytes()[..3]);
+    bytes[32..35].copy_from_slice(&header.wmta.to_le_bytes()[..3]);
+    bytes[35] = header.fvsn;
+    bytes[36] = header.luts;
+    bytes[37] = header.mode_count;
+    bytes[38] = header.temp_range_count;
+    bytes[39] = header.advanced_wfm_flags;
+    bytes[40] = header.eb;
+    bytes[41] = header.sb;
+
+    bytes[31] = checksum::additive_checksum(bytes[CHECKSUM1_RANGE].iter());
+    bytes[47] = checksum::additive_checksum(bytes[CHECKSUM2_RANGE].iter());
+
+    bytes
+}
+
+fn pointer(pointer: u32, output: &mut Vec<u8>) {
+    let bytes = &pointer.to_le_bytes()[..3];
+    output.extend_from_slice(bytes);
+    output.push(checksum::additive_checksum(bytes.iter()));
+}
+
+fn sized_block(field: &str, bytes: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+    let len: u8 = bytes
+        .len()
+        .try_into()
+        .map_err(|_| ErrorKind::InvalidLayout(format!("{field} is longer than 255 bytes")))?;
+
+    output.push(len);
+    output.extend_from_slice(bytes);
+    output.push(checksum::additive_checksum(
+        [len].iter().chain(bytes.iter()),
+    ));
+
+    Ok(())
+}
+
+/// Encode a waveform in the same order `waveform()` decodes it, using
+/// repeat counts for runs and switching to literal cells where that is
+/// shorter.
+fn waveform(waveform: &Waveform, bit_depth: BitDepth) -> Vec<u8> {
+    let mut runs: Vec<(u8, usize)> = vec![];
+
+    for matrix in waveform.iter() {
+        for n in 0..bit_depth.cells_per_frame() {
+            let cell = matrix.cells[bit_depth.cell_index(n)];
+            match runs.last_mut() {
+                Some((last, count)) if *last == cell && *count < MAX_RUN => *count += 1,
+                _ => runs.push((cell, 1)),
+            }
+        }
+    }
+
+    let mut output = vec![];
+    let mut k = 0;
+
+    while k < runs.len() {
+        let singles = runs[k..]
+            .iter()
+            .take_while(|(_, count)| *count == 1)
+            .count();
+
+        // Two toggle bytes are only worth it for three or more single cells.
+        if singles >= 3 {
+            output.push(REPEAT_TOGGLE);
+            output.extend(runs[k..k + singles].iter().map(|(cell, _)| cell));
+            output.push(REPEAT_TOGGLE);
+            k += singles;
+        } else {
+            let (cell, count) = runs[k];
+            output.push(cell);
+            output.push((count - 1) as u8);
+            k += 1;
+        }
+    }
+
+    output.push(END_OF_BLOCK);
+    output
+}
+
+fn checked_u24(field: &str, value: usize) -> Result<u32, Error> {
+    if value >= 1 << 24 {
+        return Err(
+            ErrorKind::InvalidLayout(format!("{field} does not fit in 24 bits: {value}")).into(),
+        );
+    }
+
+    Ok(value as u32)
+}
+
+/// `luts` with the bits giving the bit depth set to `bit_depth`, so that the
+/// file is read back with the bit depth it is written with.
+fn luts(luts: u8, bit_depth: BitDepth) -> u8 {
+    if BitDepth::from_luts(luts) == bit_depth {
+        return luts;
+    }
+
+    match bit_depth {
+        BitDepth::Four => luts & !0x0C,
+        BitDepth::Five => (luts & !0x0C) | 0x04,
+    }
+}
+
+impl Table<'_> {
+    /// Serialize the table to a .wbf file.
+    ///
+    /// The header fields describing the layout of the file (size, addresses,
+    /// mode and temperature counts) and all checksums are recomputed, and the
+    /// bit depth bits of `luts` follow [`Table::bit_depth`], which can differ
+    /// from the header with [`ParseOptions::bit_depth`]. The other fields are
+    /// taken from [`Table::info`].
+    ///
+    /// [`ParseOptions::bit_depth`]: super::ParseOptions::bit_depth
+    pub fn write<W: Write>(&self, output: &mut W) -> Result<(), Error> {
+        let mode_count = self.block_indices.len();
+        let temp_range_count = self.temperatures.len().saturating_sub(1);
+
+        if mode_count == 0 || mode_count > 256 {
+            return Err(
+                ErrorKind::InvalidLayout(format!("cannot store {mode_count} modes")).into(),
+            );
+        }
+
+        if temp_range_count == 0 || temp_range_count > 256 {
+            return Err(ErrorKind::InvalidLayout(format!(
+                "cannot store {temp_range_count} temperature ranges"
+            ))
+            .into());
+        }
+
+        if let Some(i) = self
+            .block_indices
+            .iter()
+            .position(|indices| indices.len() != temp_range_count)
+        {
+            return Err(ErrorKind::InvalidLayout(format!(
+                "mode {i} has {} waveforms for {temp_range_count} temperature ranges",
+                self.block_indices[i].len()
+            ))
+            .into());
+        }
+
+        let mut body = vec![];
+
+        body.extend_from_slice(&self.temperatures);
+        body.push(checksum::additive_checksum(self.temperatures.iter()));
+
+        let filename_addr = HEADER_LENGTH + body.len();
+        sized_block("filename", &self.info.filename, &mut body)?;
+
+        let wmta = HEADER_LENGTH + body.len();
+        let temperature_tables_addr = wmta + mode_count * POINTER_LENGTH as usize;
+        let mut blocks_addr =
+            temperature_tables_addr + mode_count * temp_range_count * POINTER_LENGTH as usize;
+
+        let mut extra_info = vec![];
+        let extra_info_addr = match &self.info.extra_info {
+            None => 0,
+            Some(bytes) if *bytes == self.info.filename => filename_addr,
+            Some(bytes) => {
+                sized_block("extra info", bytes, &mut extra_info)?;
+                let addr = blocks_addr;
+                blocks_addr += extra_info.len();
+                addr
+            }
+        };
+
+        for i in 0..mode_count {
+            let addr = temperature_tables_addr + i * temp_range_count * POINTER_LENGTH as usize;
+            pointer(checked_u24("mode table pointer", addr)?, &mut body);
+        }
+
+        // Blocks shared between modes or temperature ranges are only written once.
+        let mut blocks = vec![];
+        let mut block_addrs = Vec::with_capacity(self.blocks.len());
+
+        for i in 0..self.blocks.len() {
+            block_addrs.push(checked_u24("waveform pointer", blocks_addr + blocks.len())?);
+            blocks.extend(waveform(self.block(i)?, self.bit_depth));
+        }
+
+        for indices in self.block_indices.iter() {
+            for index in indices.iter() {
+                pointer(block_addrs[*index], &mut body);
+            }
+        }
+
+        body.extend(extra_info);
+        body.extend(blocks);
+
+        let filesize: u32 = (HEADER_LENGTH + body.len())
+            .try_into()
+            .map_err(|_| ErrorKind::InvalidLayout("file is larger than 4 GiB".to_string()))?;
+
+        let header = Header {
+            checksum: 0,
+            filesize,
+            serial: self.info.serial,
+            run_type: self.info.run_type.into(),
+            fpl_platform: self.info.fpl_platform,
+            fpl_lot: self.info.fpl_lot,
+            adhesive_run: self.info.adhesive_run,
+            waveform_version: self.info.waveform_version,
+            waveform_subversion: self.info.waveform_subversion,
+            waveform_type: self.info.waveform_type,
+            fpl_size: self.info.fpl_size.into(),
+            mfg_code: self.info.manufacturer.code(),
+            waveform_revision: self.info.waveform_revision,
+            old_frame_rate: self.info.old_frame_rate,
+            frame_rate: self.info.frame_rate,
+            vcom_offset: self.info.vcom_offset,
+            extra_info_addr: checked_u24("extra info address", extra_info_addr)?,
+            checksum1: 0,
+            wmta: checked_u24("mode table address", wmta)?,
+            fvsn: self.info.fvsn,
+            luts: luts(self.info.luts, self.bit_depth),
+            mode_count: (mode_count - 1) as u8,
+            temp_range_count: (temp_range_count - 1) as u8,
+            advanced_wfm_flags: self.info.advanced_wfm_flags,
+            eb: self.info.eb,
+            sb: self.info.sb,
+            checksum2: 0,
+        };
+
+        let mut header = header_bytes(&header);
+
+        let mut crc = checksum::Crc32::new();
+        crc.update(&header[4..]);
+        crc.update(&body);
+        header[0..4].copy_from_slice(&crc.finish().to_le_bytes());
+
+        output.write_all(&header)?;
+        output.write_all(&body)?;
+
+        Ok(())
+    }
+}
+
+#[test]
+fn write_round_trip_test() {
+    use super::{EXAMPLE_FILE, WaveformInfo};
+
+    let parsed = Table::parse(&mut std::io::Cursor::new(EXAMPLE_FILE)).unwrap();
+    assert_eq!(parsed.temperatures, [0, 20, 50, 50]);
+    assert_eq!(parsed.info.filename, WaveformInfo::example().filename);
+    assert_eq!(parsed.info.extra_info, WaveformInfo::example().extra_info);
+    assert_eq!(parsed.info.serial, WaveformInfo::example().serial);
+    assert_eq!(
+        parsed.info.manufacturer,
+        WaveformInfo::example().manufacturer
+    );
+    assert_eq!(parsed.info.mode_count, 2);
+    assert_eq!(parsed.info.temp_range_count, 2);
+
+    // A table built from the parsed waveforms writes the same file.
+    let waveforms = (0..3)
+        .map(|i| {
+            (0..3)
+                .map(|j| parsed.waveform(i, j).unwrap().clone())
+                .collect()
+        })
+        .collect();
+    let table = Table::new(
+        WaveformInfo::example(),
+        parsed.temperatures.clone(),
+        waveforms,
+    );
+
+    let mut bytes = vec![];
+    table.write(&mut bytes).unwrap();
+    assert_eq!(bytes, EXAMPLE_FILE);
+}